@@ -15,8 +15,9 @@ use crate::state::WallyState;
 pub mod winit;
 
 pub trait Backend {
-    const HAS_RELATIVE_MOTION: bool = false;
-    const HAS_GESTURES: bool = false;
+    /// Whether the mode and transform of outputs can be changed at runtime, configured
+    /// transforms are rejected otherwise
+    const CONFIGURABLE_OUTPUTS: bool = false;
     fn seat_name(&self) -> String;
     fn reset_buffers(&mut self, output: &Output);
     fn early_import(&mut self, surface: &WlSurface);
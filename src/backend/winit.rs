@@ -1,8 +1,11 @@
 use std::{sync::atomic::Ordering, time::Duration};
 
 use crate::{
-    elements::{self, pointer::PointerElement, CustomRenderElement},
+    capture::{self, CAPTURE_FORMAT},
+    elements::{self, pointer::PointerElement},
     monitor::Monitor,
+    output::create_output_global,
+    types::output_config::OutputSerial,
     WallyState,
 };
 use smithay::{
@@ -10,7 +13,6 @@ use smithay::{
         allocator::dmabuf::Dmabuf,
         renderer::{
             damage::{Error as OutputDamageTrackerError, OutputDamageTracker},
-            element::AsRenderElements,
             gles::GlesRenderer,
             ImportDma, ImportEgl, ImportMemWl,
         },
@@ -18,7 +20,7 @@ use smithay::{
         SwapBuffersError,
     },
     delegate_dmabuf,
-    desktop::space::render_output,
+    desktop::utils::send_frames_surface_tree,
     input::keyboard::LedState,
     output::{Mode, Output, PhysicalProperties, Subpixel},
     reexports::{
@@ -26,7 +28,7 @@ use smithay::{
         wayland_server::{protocol::wl_surface::WlSurface, Display},
         winit::platform::pump_events::PumpStatus,
     },
-    utils::{Scale, Transform},
+    utils::{Physical, Point, Scale, Transform},
     wayland::dmabuf::{DmabufGlobal, DmabufHandler, DmabufState, ImportNotifier},
 };
 use tracing::{error, info, warn};
@@ -104,16 +106,15 @@ pub fn init() -> Result<(), Box<dyn std::error::Error>> {
     };
 
     let output = Output::new("winit".to_string(), output_properties);
+    // the window has no EDID, a fixed serial still lets `outputs` sections match on it
+    output
+        .user_data()
+        .insert_if_missing(|| OutputSerial("0".into()));
 
     // Clients can access the global objects to get the physical properties and output state.
-    let _global = output.create_global::<WallyState<WinitData>>(&display_handle);
+    create_output_global::<WinitData>(&output, &display_handle);
 
-    output.change_current_state(
-        Some(mode),
-        Some(Transform::Flipped180),
-        None,
-        Some((0, 0).into()),
-    );
+    output.change_current_state(Some(mode), None, None, None);
 
     output.set_preferred(mode);
 
@@ -138,13 +139,41 @@ pub fn init() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut state = WallyState::new(display, event_loop.handle(), winit_data);
 
+    // dmabuf screen captures can only be offered if we know which device renders them
+    if let Some(feedback) = state.backend_data.dmabuf.feedback.as_ref() {
+        let modifiers = state
+            .backend_data
+            .backend
+            .renderer()
+            .egl_context()
+            .dmabuf_render_formats()
+            .iter()
+            .filter(|format| format.code == CAPTURE_FORMAT)
+            .map(|format| u64::from(format.modifier))
+            .collect();
+
+        state
+            .capture_state
+            .set_dmabuf(feedback.main_device(), modifiers);
+    }
+
     // update the global shared memory formats to the
     // smh formats supported by the backend's renderer
     state
         .shm_state
         .update_formats(state.backend_data.backend.renderer().shm_formats());
 
-    state.space.map_output(monitor.output_ref(), (0, 0));
+    // the framebuffer of the window is presented upside down, a configured transform is
+    // rejected when the output is configured so this one stays
+    monitor
+        .output_ref()
+        .change_current_state(None, Some(Transform::Flipped180), None, None);
+
+    // the window is resized instead of switching modes, and can't be disabled
+    if !state.configure_output(monitor.output_ref()) {
+        warn!("The winit output can't be disabled");
+        state.space.map_output(monitor.output_ref(), (0, 0));
+    }
 
     // add the monitor to the current compositor state
     state.add_monitor(monitor);
@@ -155,15 +184,19 @@ pub fn init() -> Result<(), Box<dyn std::error::Error>> {
         let status = winit_event_loop.dispatch_new_events(|event| match event {
             WinitEvent::Resized { size, .. } => {
                 let output = state.monitors.iter().next().unwrap().output_ref();
-                state.space.map_output(&output, (0, 0));
+                state.space.map_output(&output, output.current_location());
 
+                // only the size follows the window, keep whatever refresh rate the output has
                 let mode = Mode {
                     size,
-                    refresh: 60_000,
+                    refresh: output.current_mode().map_or(60_000, |mode| mode.refresh),
                 };
 
                 output.change_current_state(Some(mode), None, None, None);
                 output.set_preferred(mode);
+
+                state.configure_lock_surfaces();
+                state.output_configuration_changed();
             }
             WinitEvent::Input(event) => state.process_input_event(event),
             _ => (),
@@ -174,6 +207,7 @@ pub fn init() -> Result<(), Box<dyn std::error::Error>> {
             break;
         }
 
+        state.animate_workspace_swipe();
         draw(&mut state, &mut pointer_element);
 
         // dispatch all pending events accumulated during the draw routine
@@ -189,6 +223,8 @@ pub fn init() -> Result<(), Box<dyn std::error::Error>> {
             state.space.refresh();
             // ...as well as clean up some internal popup resources...
             state.popups.cleanup();
+            // ...update whether idle is inhibited by what is visible now...
+            state.refresh_idle_inhibit();
 
             // ...and lastly we flush outgoing buffers into their respective sockets
             display_handle.flush_clients().unwrap();
@@ -205,45 +241,41 @@ fn draw(state: &mut WallyState<WinitData>, pointer: &mut PointerElement) {
 
     let output_scale = Scale::from(output.current_scale().fractional_scale());
 
-    let (cursor_visible, cursor_location) = state.get_cursor_data(output_scale);
+    let (cursor_visible, cursor_location, cursor_buffer) = state.get_cursor_data(output_scale);
 
-    pointer.set_status(state.cursor_status.clone());
+    pointer.set_status(state.current_cursor_status().clone());
+    pointer.set_buffer(cursor_buffer);
+
+    let locked = state.is_locked();
+    let lock_surface = state.lock_surface(&output);
 
     let backend = &mut state.backend_data.backend;
+    let damage_tracker = &mut state.backend_data.damage_tracker;
 
     let full_redraw = &mut state.backend_data.full_redraw;
     *full_redraw = full_redraw.saturating_sub(1);
 
     let render_result = backend.bind().and_then(|_| {
-        let mut elements = Vec::<CustomRenderElement>::new();
-
-        elements.extend(pointer.render_elements(
-            backend.renderer(),
-            cursor_location,
-            output_scale,
-            1.0,
-        ));
-
         let age = if *full_redraw > 0 {
             0
         } else {
             backend.buffer_age().unwrap_or(0)
         };
 
-        render_output(
-            &output,
-            backend.renderer(),
-            1.0, // alpha
-            age,
-            [&state.space],
-            elements.as_slice(),
-            &mut state.backend_data.damage_tracker,
-            [0.0, 0.0, 0.0, 1.0], // black reset color
-        )
-        .map_err(|err| match err {
-            OutputDamageTrackerError::Rendering(err) => err.into(),
-            _ => unreachable!(),
-        })
+        let renderer = backend.renderer();
+        let pointer = Some((&*pointer, cursor_location));
+        let elements = if locked {
+            elements::lock_elements(renderer, &output, lock_surface.as_ref(), pointer)
+        } else {
+            elements::output_elements(renderer, &state.space, &output, pointer)
+        };
+
+        damage_tracker
+            .render_output(renderer, age, &elements, [0.0, 0.0, 0.0, 1.0]) // black reset color
+            .map_err(|err| match err {
+                OutputDamageTrackerError::Rendering(err) => err.into(),
+                _ => unreachable!(),
+            })
     });
 
     match render_result {
@@ -256,6 +288,19 @@ fn draw(state: &mut WallyState<WinitData>, pointer: &mut PointerElement) {
 
             backend.window().set_cursor_visible(cursor_visible);
 
+            // the only output has been blanked now
+            state.confirm_lock();
+
+            if let Some(lock_surface) = lock_surface {
+                send_frames_surface_tree(
+                    lock_surface.wl_surface(),
+                    &output,
+                    state.start_time.elapsed(),
+                    Some(Duration::ZERO),
+                    |_, _| Some(output.clone()),
+                );
+            }
+
             state.space.elements().for_each(|window| {
                 window.send_frame(
                     &output,
@@ -268,7 +313,47 @@ fn draw(state: &mut WallyState<WinitData>, pointer: &mut PointerElement) {
         Err(SwapBuffersError::ContextLost(err)) => {
             error!("Critical rendering error: {err}");
             state.running.store(false, Ordering::SeqCst);
+            return;
         }
         Err(err) => warn!("Rendering error: {err}"),
     }
+
+    process_captures(state, pointer, cursor_location);
+}
+
+/// Render pending screen captures using the same elements as the output itself
+fn process_captures(
+    state: &mut WallyState<WinitData>,
+    pointer: &PointerElement,
+    cursor_location: Point<i32, Physical>,
+) {
+    for request in state.capture_state.take_pending() {
+        if !request.frame.alive() {
+            continue;
+        }
+
+        let cursor = request.paint_cursor.then_some((pointer, cursor_location));
+        let lock_surface = state
+            .is_locked()
+            .then(|| state.lock_surface(&request.output));
+
+        let renderer = state.backend_data.backend.renderer();
+        let elements = match lock_surface {
+            Some(lock_surface) => {
+                elements::lock_elements(renderer, &request.output, lock_surface.as_ref(), cursor)
+            }
+            None => elements::output_elements(renderer, &state.space, &request.output, cursor),
+        };
+        let damage_tracker = state.capture_state.damage_tracker(&request);
+
+        match capture::render(renderer, &request, elements, damage_tracker) {
+            Ok(Some(damage)) => request.frame.ready(&damage, state.clock.now().into()),
+            // nothing changed yet, try again on the next frame
+            Ok(None) => state.capture_state.queue(request),
+            Err(err) => {
+                warn!("Failed to capture output: {err}");
+                request.frame.failed();
+            }
+        }
+    }
 }
@@ -0,0 +1,279 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{bail, Result};
+use smithay::{
+    backend::{
+        allocator::{Buffer as _, Fourcc},
+        renderer::{
+            buffer_type,
+            damage::OutputDamageTracker,
+            element::utils::{Relocate, RelocateRenderElement},
+            gles::{GlesRenderer, GlesTexture},
+            Bind, BufferType, ExportMem, Offscreen, Unbind,
+        },
+    },
+    output::Output,
+    reexports::{
+        wayland_protocols::ext::image_copy_capture::v1::server::ext_image_copy_capture_frame_v1::{
+            self, ExtImageCopyCaptureFrameV1,
+        },
+        wayland_protocols_wlr::screencopy::v1::server::zwlr_screencopy_frame_v1::{
+            self, ZwlrScreencopyFrameV1,
+        },
+        wayland_server::{
+            backend::ObjectId,
+            protocol::{wl_buffer::WlBuffer, wl_output, wl_shm},
+            Resource,
+        },
+    },
+    utils::{Buffer as BufferCoord, Logical, Physical, Point, Rectangle, Size, Transform},
+    wayland::{
+        dmabuf::get_dmabuf,
+        shm::{with_buffer_contents, with_buffer_contents_mut},
+    },
+};
+
+use crate::elements::OutputRenderElement;
+
+/// The only format captures are rendered in
+pub const CAPTURE_FORMAT: Fourcc = Fourcc::Argb8888;
+
+/// A request to copy the contents of an output into a client buffer,
+/// queued by the capture protocols and rendered by the backend
+#[derive(Debug)]
+pub struct CaptureRequest {
+    pub output: Output,
+    /// Region of the output to capture, relative to the output
+    pub region: Rectangle<i32, Physical>,
+    pub buffer: WlBuffer,
+    pub paint_cursor: bool,
+    /// Only complete the capture once the captured region has been damaged
+    pub wait_for_damage: bool,
+    /// Identifies the damage tracker used between successive captures
+    pub session: ObjectId,
+    pub frame: CaptureFrame,
+}
+
+#[derive(Debug)]
+pub enum CaptureFrame {
+    Screencopy {
+        frame: ZwlrScreencopyFrameV1,
+        with_damage: bool,
+    },
+    ImageCopy(ExtImageCopyCaptureFrameV1),
+}
+
+impl CaptureFrame {
+    pub fn alive(&self) -> bool {
+        match self {
+            CaptureFrame::Screencopy { frame, .. } => frame.is_alive(),
+            CaptureFrame::ImageCopy(frame) => frame.is_alive(),
+        }
+    }
+
+    pub fn ready(&self, damage: &[Rectangle<i32, Physical>], time: Duration) {
+        let tv_sec_hi = (time.as_secs() >> 32) as u32;
+        let tv_sec_lo = time.as_secs() as u32;
+        let tv_nsec = time.subsec_nanos();
+
+        match self {
+            CaptureFrame::Screencopy { frame, with_damage } => {
+                frame.flags(zwlr_screencopy_frame_v1::Flags::empty());
+                if *with_damage {
+                    for rect in damage {
+                        frame.damage(
+                            rect.loc.x as u32,
+                            rect.loc.y as u32,
+                            rect.size.w as u32,
+                            rect.size.h as u32,
+                        );
+                    }
+                }
+                frame.ready(tv_sec_hi, tv_sec_lo, tv_nsec);
+            }
+            CaptureFrame::ImageCopy(frame) => {
+                frame.transform(wl_output::Transform::Normal);
+                for rect in damage {
+                    frame.damage(rect.loc.x, rect.loc.y, rect.size.w, rect.size.h);
+                }
+                frame.presentation_time(tv_sec_hi, tv_sec_lo, tv_nsec);
+                frame.ready();
+            }
+        }
+    }
+
+    pub fn failed(&self) {
+        match self {
+            CaptureFrame::Screencopy { frame, .. } => frame.failed(),
+            CaptureFrame::ImageCopy(frame) => {
+                frame.failed(ext_image_copy_capture_frame_v1::FailureReason::Unknown)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CaptureState {
+    pending: Vec<CaptureRequest>,
+    damage_trackers: HashMap<ObjectId, (Rectangle<i32, Physical>, OutputDamageTracker)>,
+    /// Render node and `CAPTURE_FORMAT` modifiers offered for dmabuf captures
+    dmabuf: Option<(u64, Vec<u64>)>,
+}
+
+impl CaptureState {
+    pub fn queue(&mut self, request: CaptureRequest) {
+        self.pending.push(request);
+    }
+
+    pub fn take_pending(&mut self) -> Vec<CaptureRequest> {
+        std::mem::take(&mut self.pending)
+    }
+
+    pub fn remove_session(&mut self, session: &ObjectId) {
+        self.damage_trackers.remove(session);
+    }
+
+    pub fn set_dmabuf(&mut self, device: u64, modifiers: Vec<u64>) {
+        self.dmabuf = Some((device, modifiers));
+    }
+
+    pub fn dmabuf(&self) -> Option<&(u64, Vec<u64>)> {
+        self.dmabuf.as_ref()
+    }
+
+    /// Get the damage tracker of the session of a request,
+    /// replacing it if the captured region changed
+    pub fn damage_tracker(&mut self, request: &CaptureRequest) -> &mut OutputDamageTracker {
+        let scale = request.output.current_scale().fractional_scale();
+
+        let (region, damage_tracker) = self
+            .damage_trackers
+            .entry(request.session.clone())
+            .or_insert_with(|| {
+                (
+                    request.region,
+                    OutputDamageTracker::new(request.region.size, scale, Transform::Normal),
+                )
+            });
+
+        if *region != request.region {
+            *region = request.region;
+            *damage_tracker =
+                OutputDamageTracker::new(request.region.size, scale, Transform::Normal);
+        }
+
+        damage_tracker
+    }
+}
+
+/// The capturable area of an output, or the part of it covered by `region`
+pub fn output_region(
+    output: &Output,
+    region: Option<Rectangle<i32, Logical>>,
+) -> Option<Rectangle<i32, Physical>> {
+    let mode = output.current_mode()?;
+    let size = output.current_transform().transform_size(mode.size);
+    let bounds = Rectangle::new((0, 0).into(), size);
+
+    match region {
+        Some(region) => region
+            .to_physical_precise_round(output.current_scale().fractional_scale())
+            .intersection(bounds),
+        None => Some(bounds),
+    }
+}
+
+/// Check that a client buffer can hold a capture of the given size
+pub fn buffer_matches(buffer: &WlBuffer, size: Size<i32, Physical>) -> bool {
+    match buffer_type(buffer) {
+        Some(BufferType::Shm) => with_buffer_contents(buffer, |_, _, data| {
+            data.format == wl_shm::Format::Argb8888
+                && data.width == size.w
+                && data.height == size.h
+                && data.stride >= size.w * 4
+        })
+        .unwrap_or(false),
+        Some(BufferType::Dma) => get_dmabuf(buffer).is_ok_and(|dmabuf| {
+            let buffer_size = dmabuf.size();
+            dmabuf.format().code == CAPTURE_FORMAT
+                && buffer_size.w == size.w
+                && buffer_size.h == size.h
+        }),
+        _ => false,
+    }
+}
+
+/// Render the elements of an output into the buffer of a capture request.
+///
+/// Returns the damage since the previous capture of the same session, or `None`
+/// if the request waits for damage and the captured region is unchanged.
+pub fn render(
+    renderer: &mut GlesRenderer,
+    request: &CaptureRequest,
+    elements: Vec<OutputRenderElement>,
+    damage_tracker: &mut OutputDamageTracker,
+) -> Result<Option<Vec<Rectangle<i32, Physical>>>> {
+    let region = request.region;
+    let offset = Point::<i32, Physical>::from((-region.loc.x, -region.loc.y));
+
+    let elements: Vec<_> = elements
+        .into_iter()
+        .map(|element| RelocateRenderElement::from_element(element, offset, Relocate::Relative))
+        .collect();
+
+    let (damage, _) = damage_tracker.damage_output(1, &elements)?;
+    let damage = damage.unwrap_or_default();
+
+    if request.wait_for_damage && damage.is_empty() {
+        return Ok(None);
+    }
+
+    let scale = request.output.current_scale().fractional_scale();
+    let mut render_tracker = OutputDamageTracker::new(region.size, scale, Transform::Normal);
+
+    match buffer_type(&request.buffer) {
+        Some(BufferType::Shm) => {
+            let size = Size::<i32, BufferCoord>::from((region.size.w, region.size.h));
+            let texture: GlesTexture = renderer.create_buffer(CAPTURE_FORMAT, size)?;
+
+            renderer.bind(texture)?;
+            render_tracker.render_output(renderer, 0, &elements, [0.0, 0.0, 0.0, 1.0])?;
+
+            let mapping =
+                renderer.copy_framebuffer(Rectangle::new((0, 0).into(), size), CAPTURE_FORMAT)?;
+            let pixels = renderer.map_texture(&mapping)?;
+
+            with_buffer_contents_mut(&request.buffer, |ptr, len, data| {
+                let row_length = data.width as usize * 4;
+                for row in 0..data.height as usize {
+                    let src = row * row_length;
+                    let dst = data.offset as usize + row * data.stride as usize;
+                    if src + row_length > pixels.len() || dst + row_length > len {
+                        break;
+                    }
+                    // SAFETY: both ranges were bounds checked above
+                    unsafe {
+                        std::ptr::copy_nonoverlapping(
+                            pixels.as_ptr().add(src),
+                            ptr.add(dst),
+                            row_length,
+                        );
+                    }
+                }
+            })?;
+        }
+        Some(BufferType::Dma) => {
+            let dmabuf = get_dmabuf(&request.buffer)?.clone();
+
+            renderer.bind(dmabuf)?;
+            let result =
+                render_tracker.render_output(renderer, 0, &elements, [0.0, 0.0, 0.0, 1.0])?;
+            let _ = result.sync.wait();
+        }
+        _ => bail!("unsupported buffer type"),
+    }
+
+    renderer.unbind()?;
+
+    Ok(Some(damage))
+}
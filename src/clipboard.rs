@@ -0,0 +1,224 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{ErrorKind, Read, Write},
+    os::fd::OwnedFd,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use smithay::{
+    input::Seat,
+    reexports::{
+        calloop::{
+            channel::{self, Sender},
+            LoopHandle,
+        },
+        rustix::pipe::{pipe_with, PipeFlags},
+        wayland_server::{backend::ClientId, Resource},
+    },
+    wayland::selection::data_device::{
+        request_data_device_client_selection, set_data_device_selection,
+    },
+};
+use tracing::{debug, warn};
+
+use crate::{backend::Backend, config::CONFIG, state::WallyState};
+
+/// Data of a compositor-owned selection, by mime type
+pub type ClipboardContents = Arc<HashMap<String, Vec<u8>>>;
+
+/// Keeps a copy of the clipboard so it outlives the client that owns it
+#[derive(Debug)]
+pub struct ClipboardKeeper {
+    /// Bumped with every new client selection, so reads of a replaced selection are dropped
+    serial: u64,
+    contents: HashMap<String, Vec<u8>>,
+    /// Client that owned the selection when it was copied. Clipboard selections can only be
+    /// set by the client with keyboard focus, data-control clients aside
+    owner: Option<ClientId>,
+    sender: Sender<ClipboardRead>,
+}
+
+#[derive(Debug)]
+pub struct ClipboardRead {
+    serial: u64,
+    mime_type: String,
+    /// `None` if reading failed or the data exceeded the size limit
+    data: Option<Vec<u8>>,
+}
+
+impl ClipboardKeeper {
+    pub fn new<BackendData: Backend + 'static>(
+        loop_handle: &LoopHandle<'static, WallyState<BackendData>>,
+    ) -> Self {
+        let (sender, receiver) = channel::channel();
+
+        loop_handle
+            .insert_source(receiver, |event, _, state| {
+                if let channel::Event::Msg(read) = event {
+                    state.clipboard_keeper.finish_read(read);
+                }
+            })
+            .expect("Failed to init the clipboard keeper source.");
+
+        Self {
+            serial: 0,
+            contents: HashMap::new(),
+            owner: None,
+            sender,
+        }
+    }
+
+    fn finish_read(&mut self, read: ClipboardRead) {
+        if read.serial != self.serial {
+            return;
+        }
+
+        let Some(data) = read.data else {
+            debug!(mime_type = read.mime_type, "Not keeping clipboard data");
+            return;
+        };
+
+        self.contents.insert(read.mime_type, data);
+    }
+}
+
+impl<BackendData: Backend> WallyState<BackendData> {
+    /// Start copying every mime type offered by the new clipboard selection
+    pub fn keep_clipboard(&mut self, mime_types: Vec<String>, seat: Seat<Self>) {
+        let owner = seat
+            .get_keyboard()
+            .and_then(|keyboard| keyboard.current_focus())
+            .and_then(|focus| focus.client())
+            .map(|client| client.id());
+
+        let keeper = &mut self.clipboard_keeper;
+        keeper.serial += 1;
+        keeper.contents.clear();
+        keeper.owner = owner;
+
+        let serial = keeper.serial;
+        // shared by all mime types, which are read at the same time
+        let budget = Arc::new(AtomicUsize::new(CONFIG.clipboard_keeper_max_size));
+
+        // the selection is only readable once the handler returned
+        self.loop_handle.insert_idle(move |state| {
+            if state.clipboard_keeper.serial != serial {
+                return;
+            }
+
+            for mime_type in mime_types {
+                let (read_fd, write_fd) = match pipe_with(PipeFlags::CLOEXEC) {
+                    Ok(fds) => fds,
+                    Err(err) => {
+                        warn!("Failed to create clipboard pipe: {err}");
+                        return;
+                    }
+                };
+
+                if request_data_device_client_selection(&seat, mime_type.clone(), write_fd).is_err()
+                {
+                    return;
+                }
+
+                let sender = state.clipboard_keeper.sender.clone();
+                let budget = budget.clone();
+                std::thread::spawn(move || {
+                    let data = read_within_budget(File::from(read_fd), &budget);
+                    let _ = sender.send(ClipboardRead {
+                        serial,
+                        mime_type,
+                        data,
+                    });
+                });
+            }
+        });
+    }
+
+    /// The clipboard was cleared, which is either its owner going away or clearing it on purpose,
+    /// e.g. a password manager. Only the former is known once the disconnect has been processed
+    pub fn clipboard_cleared(&mut self, seat: Seat<Self>) {
+        let serial = self.clipboard_keeper.serial;
+
+        self.loop_handle.insert_idle(move |state| {
+            let keeper = &mut state.clipboard_keeper;
+            if keeper.serial != serial {
+                return;
+            }
+
+            let owner_alive = keeper
+                .owner
+                .clone()
+                .is_some_and(|owner| state.display_handle.get_client(owner).is_ok());
+
+            if owner_alive {
+                keeper.serial += 1;
+                keeper.contents.clear();
+            } else {
+                state.restore_clipboard(&seat);
+            }
+        });
+    }
+
+    /// Offer the kept clipboard as a compositor-owned selection
+    pub fn restore_clipboard(&mut self, seat: &Seat<Self>) {
+        let keeper = &mut self.clipboard_keeper;
+        keeper.serial += 1;
+        keeper.owner = None;
+
+        if keeper.contents.is_empty() {
+            return;
+        }
+
+        let contents: ClipboardContents = Arc::new(std::mem::take(&mut keeper.contents));
+        let mime_types = contents.keys().cloned().collect();
+
+        set_data_device_selection(&self.display_handle, seat, mime_types, contents);
+    }
+}
+
+/// Read clipboard data as long as the budget shared with the other mime types allows,
+/// returning `None` if reading failed or the budget ran out
+fn read_within_budget(mut file: File, budget: &AtomicUsize) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut chunk = [0; 8192];
+
+    loop {
+        let len = match file.read(&mut chunk) {
+            Ok(0) => return Some(data),
+            Ok(len) => len,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => return None,
+        };
+
+        let reserved = budget
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                left.checked_sub(len)
+            })
+            .is_ok();
+        if !reserved {
+            debug!("Clipboard size limit reached");
+            return None;
+        }
+
+        data.extend_from_slice(&chunk[..len]);
+    }
+}
+
+/// Write kept clipboard data to a client requesting it
+pub fn send_clipboard(contents: &ClipboardContents, mime_type: String, fd: OwnedFd) {
+    let contents = contents.clone();
+
+    std::thread::spawn(move || {
+        let Some(data) = contents.get(&mime_type) else {
+            return;
+        };
+
+        if let Err(err) = File::from(fd).write_all(data) {
+            debug!("Failed to send clipboard data: {err}");
+        }
+    });
+}
@@ -1,10 +1,17 @@
 use crate::types::keybind::KeyModifiers;
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf};
 
 use lazy_static::lazy_static;
 use smithay::input::keyboard::keysyms;
 
-use crate::types::keybind::{Action, Keybind};
+use crate::types::{
+    gesture::{GestureAction, SwipeDirection, SwipeGesture},
+    input_device::{InputDeviceConfig, InputDeviceKind},
+    keybind::{Action, Binding, BindingMode, Keybind, MouseBind, MouseTrigger},
+    output_config::OutputConfig,
+    window_rule::WindowRule,
+    xkb::XkbSettings,
+};
 
 lazy_static! {
     pub static ref CONFIG: Config = Config::new();
@@ -15,8 +22,46 @@ pub struct Config {
     pub border_color_focused: u32,
     pub border_color_unfocused: u32,
     pub workspace_count: usize,
+    /// Overrides `XCURSOR_THEME`
+    pub cursor_theme: Option<String>,
+    /// Overrides `XCURSOR_SIZE`
+    pub cursor_size: Option<u32>,
     // MAYBE: gap: u8
-    pub keybinds: HashMap<Keybind, Action>,
+    pub xkb: XkbSettings,
+    /// Milliseconds a key is held before it starts repeating
+    pub repeat_delay: i32,
+    /// Repeats per second
+    pub repeat_rate: i32,
+    pub numlock_on_start: bool,
+    /// Mode, position, scale and transform of outputs, applied as they are added
+    pub outputs: Vec<OutputConfig>,
+    /// libinput settings applied to devices as they are added
+    pub input_devices: Vec<InputDeviceConfig>,
+    pub keybinds: HashMap<Keybind, Binding>,
+    pub mousebinds: HashMap<MouseBind, Action>,
+    /// Toggles a client's inhibitor of keyboard shortcuts, e.g. to get out of a VM
+    pub shortcuts_inhibit_escape: Keybind,
+    /// Binding modes by name, entered with `Action::EnterMode`
+    pub binding_modes: HashMap<String, BindingMode>,
+    /// Outputs touchscreens are mapped to by device name, unmapped touchscreens use the first output
+    pub touch_outputs: HashMap<String, String>,
+    /// Outputs drawing tablets are mapped to by device name, unmapped tablets use the first output
+    pub tablet_outputs: HashMap<String, String>,
+    /// Touchpad swipes handled by the compositor, other swipes are forwarded to clients
+    pub swipe_gestures: HashMap<SwipeGesture, GestureAction>,
+    pub window_rules: Vec<WindowRule>,
+    /// Only offer virtual keyboards and pointers and clipboard managers to `trusted_clients`
+    pub privileged_protocols_trusted_only: bool,
+    /// Executables of clients allowed to use privileged protocols, as absolute paths with
+    /// symlinks resolved. Anything able to run code inside such a process is trusted as well
+    pub trusted_clients: Vec<PathBuf>,
+    /// Keep a copy of the clipboard, offered again once the client owning it exits
+    pub clipboard_keeper: bool,
+    /// Maximum number of bytes kept across all mime types of a selection
+    pub clipboard_keeper_max_size: usize,
+    /// Commands run by `sh` to spawn the windows of named scratchpads, the windows
+    /// are picked up by window rules with a matching `scratchpad` name
+    pub scratchpads: HashMap<String, String>,
 }
 
 impl Config {
@@ -26,29 +71,158 @@ impl Config {
             border_color_focused: 0x00ff00,
             border_color_unfocused: 0xff0000,
             workspace_count: 9,
+            cursor_theme: None,
+            cursor_size: None,
+            xkb: XkbSettings {
+                layout: "us".into(),
+                ..Default::default()
+            },
+            repeat_delay: 200,
+            repeat_rate: 25,
+            numlock_on_start: false,
+            outputs: vec![OutputConfig {
+                scale: Some(1.0),
+                ..OutputConfig::make_model("Wally", "Winit")
+            }],
+            input_devices: vec![InputDeviceConfig {
+                tap: Some(true),
+                natural_scroll: Some(true),
+                dwt: Some(true),
+                ..InputDeviceConfig::kind(InputDeviceKind::Touchpad)
+            }],
             keybinds: HashMap::from([
                 (
                     Keybind::new(KeyModifiers::SUPER | KeyModifiers::SHIFT, keysyms::KEY_q),
-                    Action::Quit,
+                    // a crashed locker leaves the outputs blanked, quitting is the way out
+                    Binding {
+                        allow_when_locked: true,
+                        allow_when_inhibited: true,
+                        ..Action::Quit.into()
+                    },
                 ),
                 (
                     Keybind::new(KeyModifiers::SUPER, keysyms::KEY_l),
-                    Action::NextWorkspace,
+                    Action::NextWorkspace.into(),
                 ),
                 (
                     Keybind::new(KeyModifiers::SUPER, keysyms::KEY_h),
-                    Action::PrevWorkspace,
+                    Action::PrevWorkspace.into(),
                 ),
                 (
                     Keybind::new(
                         KeyModifiers::SUPER | KeyModifiers::SHIFT,
                         keysyms::KEY_Return,
                     ),
-                    Action::Spawn("alacritty".into()),
+                    Action::Spawn("alacritty".into()).into(),
                 ),
                 (
                     Keybind::new(KeyModifiers::SUPER, keysyms::KEY_p),
-                    Action::Spawn("bemenu_run".into()),
+                    Action::Spawn("bemenu_run".into()).into(),
+                ),
+                (
+                    // tapping Super on its own
+                    Keybind::new(KeyModifiers::SUPER, keysyms::KEY_Super_L),
+                    Binding {
+                        on_release: true,
+                        ..Action::Spawn("bemenu_run".into()).into()
+                    },
+                ),
+                (
+                    Keybind::new(KeyModifiers::SUPER, keysyms::KEY_grave),
+                    Action::ToggleScratchpad("terminal".into()).into(),
+                ),
+                (
+                    Keybind::new(KeyModifiers::SUPER, keysyms::KEY_n),
+                    Action::ToggleScratchpad("notes".into()).into(),
+                ),
+                (
+                    Keybind::new(KeyModifiers::SUPER, keysyms::KEY_space),
+                    Action::SwitchLayout.into(),
+                ),
+                (
+                    Keybind::new(KeyModifiers::SUPER, keysyms::KEY_w),
+                    Action::EnterMode("workspace".into()).into(),
+                ),
+            ]),
+            mousebinds: HashMap::from([
+                (
+                    MouseBind::new(KeyModifiers::SUPER, MouseTrigger::ScrollDown),
+                    Action::NextWorkspace,
+                ),
+                (
+                    MouseBind::new(KeyModifiers::SUPER, MouseTrigger::ScrollUp),
+                    Action::PrevWorkspace,
+                ),
+                (
+                    MouseBind::new(KeyModifiers::SUPER, MouseTrigger::MIDDLE),
+                    Action::RemoveWindow,
+                ),
+            ]),
+            shortcuts_inhibit_escape: Keybind::new(KeyModifiers::SUPER, keysyms::KEY_Escape),
+            binding_modes: HashMap::from([(
+                "workspace".into(),
+                BindingMode {
+                    keybinds: HashMap::from([
+                        (
+                            Keybind::new(KeyModifiers::NONE, keysyms::KEY_l),
+                            Binding {
+                                repeat: true,
+                                ..Action::NextWorkspace.into()
+                            },
+                        ),
+                        (
+                            Keybind::new(KeyModifiers::NONE, keysyms::KEY_h),
+                            Binding {
+                                repeat: true,
+                                ..Action::PrevWorkspace.into()
+                            },
+                        ),
+                        (
+                            Keybind::new(KeyModifiers::NONE, keysyms::KEY_Return),
+                            Action::ExitMode.into(),
+                        ),
+                    ]),
+                    passthrough: false,
+                },
+            )]),
+            touch_outputs: HashMap::new(),
+            tablet_outputs: HashMap::new(),
+            swipe_gestures: HashMap::from([(
+                SwipeGesture::new(3, SwipeDirection::Horizontal),
+                GestureAction::SwitchWorkspace,
+            )]),
+            window_rules: vec![
+                WindowRule {
+                    floating: true,
+                    ..WindowRule::app_id("^pavucontrol$")
+                },
+                WindowRule {
+                    workspace: Some(9),
+                    ..WindowRule::app_id("^Slack$")
+                },
+                WindowRule {
+                    scratchpad: Some("terminal".into()),
+                    size: Some((1200, 600).into()),
+                    ..WindowRule::app_id("^scratchpad-terminal$")
+                },
+                WindowRule {
+                    scratchpad: Some("notes".into()),
+                    size: Some((900, 700).into()),
+                    ..WindowRule::app_id("^scratchpad-notes$")
+                },
+            ],
+            privileged_protocols_trusted_only: false,
+            trusted_clients: vec!["/usr/bin/wayvnc".into(), "/usr/bin/wtype".into()],
+            clipboard_keeper: true,
+            clipboard_keeper_max_size: 16 * 1024 * 1024,
+            scratchpads: HashMap::from([
+                (
+                    "terminal".into(),
+                    "alacritty --class scratchpad-terminal".into(),
+                ),
+                (
+                    "notes".into(),
+                    "alacritty --class scratchpad-notes -e nvim ~/notes.md".into(),
                 ),
             ]),
         }
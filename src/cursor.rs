@@ -0,0 +1,160 @@
+use std::{collections::HashMap, time::Duration};
+
+use smithay::{
+    backend::{allocator::Fourcc, renderer::element::memory::MemoryRenderBuffer},
+    input::pointer::CursorIcon,
+    utils::{Logical, Point, Transform},
+};
+use tracing::{info, warn};
+use xcursor::{
+    parser::{parse_xcursor, Image},
+    CursorTheme as XCursorTheme,
+};
+
+use crate::config::CONFIG;
+
+/// Loads named cursors from an xcursor theme and caches a render buffer
+/// for every frame of a cursor at each scale it is requested at
+pub struct CursorTheme {
+    name: String,
+    size: u32,
+    theme: XCursorTheme,
+    images: HashMap<CursorIcon, Option<Vec<Image>>>,
+    buffers: HashMap<(CursorIcon, u32, usize), MemoryRenderBuffer>,
+}
+
+impl std::fmt::Debug for CursorTheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CursorTheme")
+            .field("name", &self.name)
+            .field("size", &self.size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CursorTheme {
+    /// Load the theme set in the config, falling back to `XCURSOR_THEME` and `XCURSOR_SIZE`
+    pub fn load() -> Self {
+        let name = CONFIG
+            .cursor_theme
+            .clone()
+            .or_else(|| std::env::var("XCURSOR_THEME").ok())
+            .unwrap_or_else(|| String::from("default"));
+
+        let size = CONFIG
+            .cursor_size
+            .or_else(|| {
+                std::env::var("XCURSOR_SIZE")
+                    .ok()
+                    .and_then(|size| size.parse().ok())
+            })
+            .unwrap_or(24);
+
+        info!(name, size, "Loading cursor theme");
+
+        Self {
+            theme: XCursorTheme::load(&name),
+            name,
+            size,
+            images: HashMap::new(),
+            buffers: HashMap::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Get the buffer and hotspot of a named cursor for the given output scale.
+    ///
+    /// For animated cursors the frame is picked based on `time`.
+    pub fn image(
+        &mut self,
+        icon: CursorIcon,
+        scale: f64,
+        time: Duration,
+    ) -> Option<(MemoryRenderBuffer, Point<f64, Logical>)> {
+        let scale = (scale.ceil() as u32).max(1);
+
+        let images = self
+            .images
+            .entry(icon)
+            .or_insert_with(|| load_icon(&self.theme, icon))
+            .as_ref()?;
+
+        let (index, image) = frame(time.as_millis() as u32, self.size * scale, images);
+
+        let hotspot = Point::from((
+            image.xhot as f64 / scale as f64,
+            image.yhot as f64 / scale as f64,
+        ));
+
+        let buffer = self
+            .buffers
+            .entry((icon, scale, index))
+            .or_insert_with(|| {
+                MemoryRenderBuffer::from_slice(
+                    &image.pixels_rgba,
+                    Fourcc::Argb8888,
+                    (image.width as i32, image.height as i32),
+                    scale as i32,
+                    Transform::Normal,
+                    None,
+                )
+            })
+            .clone();
+
+        Some((buffer, hotspot))
+    }
+}
+
+fn load_icon(theme: &XCursorTheme, icon: CursorIcon) -> Option<Vec<Image>> {
+    let images = std::iter::once(icon.name())
+        .chain(icon.alt_names().iter().copied())
+        .find_map(|name| {
+            let path = theme.load_icon(name)?;
+            let content = std::fs::read(path).ok()?;
+            parse_xcursor(&content).filter(|images| !images.is_empty())
+        });
+
+    if images.is_none() {
+        warn!(?icon, "Unable to load cursor from theme");
+    }
+
+    images
+}
+
+/// Pick the frame to show at `millis` among the images closest to `size`,
+/// returning it together with its index in `images`
+fn frame(mut millis: u32, size: u32, images: &[Image]) -> (usize, &Image) {
+    let nearest_size = images
+        .iter()
+        .min_by_key(|image| (size as i32 - image.size as i32).abs())
+        .map(|image| image.size)
+        .unwrap();
+
+    let frames: Vec<(usize, &Image)> = images
+        .iter()
+        .enumerate()
+        .filter(|(_, image)| image.size == nearest_size)
+        .collect();
+
+    let total_delay: u32 = frames.iter().map(|(_, image)| image.delay).sum();
+    if total_delay == 0 {
+        return frames[0];
+    }
+
+    millis %= total_delay;
+    for &(index, image) in &frames {
+        if millis < image.delay {
+            return (index, image);
+        }
+        millis -= image.delay;
+    }
+
+    frames[0]
+}
@@ -1,6 +1,23 @@
-use pointer::PointerRenderElement;
-use smithay::{backend::renderer::gles::GlesRenderer, render_elements};
-use window::WindowRenderElement;
+use pointer::{PointerElement, PointerRenderElement};
+use smithay::{
+    backend::renderer::{
+        element::{
+            surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
+            AsRenderElements, Kind,
+        },
+        gles::GlesRenderer,
+    },
+    desktop::{
+        space::{space_render_elements, SpaceRenderElements},
+        Space,
+    },
+    output::Output,
+    render_elements,
+    utils::{Physical, Point, Scale},
+    wayland::session_lock::LockSurface,
+};
+use tracing::warn;
+use window::{WindowElement, WindowRenderElement};
 
 pub mod border;
 pub mod pointer;
@@ -12,3 +29,74 @@ render_elements! {
     Pointer=PointerRenderElement<GlesRenderer>,
     Window=WindowRenderElement,
 }
+
+// everything drawn on an output, front to back
+render_elements! {
+    pub OutputRenderElement<=GlesRenderer>;
+    Custom=CustomRenderElement,
+    Space=SpaceRenderElements<GlesRenderer, WindowRenderElement>,
+    Lock=WaylandSurfaceRenderElement<GlesRenderer>,
+}
+
+fn pointer_elements(
+    renderer: &mut GlesRenderer,
+    pointer: Option<(&PointerElement, Point<i32, Physical>)>,
+    scale: Scale<f64>,
+) -> Vec<OutputRenderElement> {
+    let Some((pointer, location)) = pointer else {
+        return Vec::new();
+    };
+
+    pointer
+        .render_elements::<CustomRenderElement>(renderer, location, scale, 1.0)
+        .into_iter()
+        .map(OutputRenderElement::Custom)
+        .collect()
+}
+
+/// Collect the render elements of an output, optionally drawing the pointer on top
+pub fn output_elements(
+    renderer: &mut GlesRenderer,
+    space: &Space<WindowElement>,
+    output: &Output,
+    pointer: Option<(&PointerElement, Point<i32, Physical>)>,
+) -> Vec<OutputRenderElement> {
+    let scale = Scale::from(output.current_scale().fractional_scale());
+
+    let mut elements = pointer_elements(renderer, pointer, scale);
+
+    match space_render_elements(renderer, [space], output, 1.0) {
+        Ok(space_elements) => {
+            elements.extend(space_elements.into_iter().map(OutputRenderElement::Space))
+        }
+        Err(err) => warn!("Failed to collect space elements: {err:?}"),
+    }
+
+    elements
+}
+
+/// Collect the render elements of a locked output, nothing but its lock surface
+/// and the pointer is drawn. Without a lock surface the output stays blank.
+pub fn lock_elements(
+    renderer: &mut GlesRenderer,
+    output: &Output,
+    lock_surface: Option<&LockSurface>,
+    pointer: Option<(&PointerElement, Point<i32, Physical>)>,
+) -> Vec<OutputRenderElement> {
+    let scale = Scale::from(output.current_scale().fractional_scale());
+
+    let mut elements = pointer_elements(renderer, pointer, scale);
+
+    if let Some(lock_surface) = lock_surface {
+        elements.extend(render_elements_from_surface_tree(
+            renderer,
+            lock_surface.wl_surface(),
+            (0, 0),
+            scale,
+            1.0,
+            Kind::Unspecified,
+        ));
+    }
+
+    elements
+}
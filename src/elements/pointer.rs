@@ -31,8 +31,8 @@ impl PointerElement {
         self.status = status;
     }
 
-    pub fn set_buffer(&mut self, buffer: MemoryRenderBuffer) {
-        self.buffer = Some(buffer);
+    pub fn set_buffer(&mut self, buffer: Option<MemoryRenderBuffer>) {
+        self.buffer = buffer;
     }
 }
 
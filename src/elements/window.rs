@@ -20,16 +20,26 @@ use smithay::{
         Seat,
     },
     output::Output,
-    reexports::wayland_server::{backend::ObjectId, protocol::wl_surface::WlSurface, Resource},
+    reexports::{
+        wayland_protocols::xdg::shell::server::xdg_toplevel,
+        wayland_server::{backend::ObjectId, protocol::wl_surface::WlSurface, Resource},
+    },
     render_elements,
     utils::{
-        user_data::UserDataMap, IsAlive, Logical, Physical, Point, Rectangle, Serial,
+        user_data::UserDataMap, IsAlive, Logical, Physical, Point, Rectangle, Serial, Size,
         SERIAL_COUNTER,
     },
-    wayland::{compositor::SurfaceData, seat::WaylandFocus},
+    wayland::{
+        compositor::{with_states, SurfaceData},
+        seat::WaylandFocus,
+        shell::xdg::XdgToplevelSurfaceData,
+    },
 };
 
-use crate::{backend::Backend, config::CONFIG, focus::PointerFocusTarget, state::WallyState};
+use crate::{
+    backend::Backend, config::CONFIG, focus::PointerFocusTarget, state::WallyState,
+    types::window_rule::WindowRule,
+};
 
 use super::border::BorderShader;
 
@@ -37,7 +47,31 @@ use super::border::BorderShader;
 pub struct WindowElement(pub Window);
 
 pub struct WindowState {
-    is_focused: bool,
+    pub is_focused: bool,
+    pub is_floating: bool,
+    pub has_border: bool,
+    pub opacity: f32,
+    app_id: Option<String>,
+    title: Option<String>,
+    /// Surface of the window the pointer is over, e.g. a subsurface or popup,
+    /// with its location relative to the window
+    pointer_surface: Option<(WlSurface, Point<i32, Logical>)>,
+}
+
+impl Default for WindowState {
+    fn default() -> Self {
+        // NOTE: we set focus to true when spawning a new window state
+        //       since we will want the window to be focused on creation
+        Self {
+            is_focused: true,
+            is_floating: false,
+            has_border: true,
+            opacity: 1.0,
+            app_id: None,
+            title: None,
+            pointer_surface: None,
+        }
+    }
 }
 
 impl WindowElement {
@@ -58,10 +92,8 @@ impl WindowElement {
     }
 
     pub fn window_state(&self) -> RefMut<'_, WindowState> {
-        // NOTE: we set focus to true when spawning a new window state
-        //       since we will want the window to be focused on creation
         self.user_data()
-            .insert_if_missing(|| RefCell::new(WindowState { is_focused: true }));
+            .insert_if_missing(|| RefCell::new(WindowState::default()));
 
         self.user_data()
             .get::<RefCell<WindowState>>()
@@ -95,6 +127,92 @@ impl WindowElement {
         self.0.on_commit();
     }
 
+    pub fn initial_configure_sent(&self) -> bool {
+        match self.0.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => with_states(toplevel.wl_surface(), |states| {
+                states
+                    .data_map
+                    .get::<XdgToplevelSurfaceData>()
+                    .unwrap()
+                    .lock()
+                    .unwrap()
+                    .initial_configure_sent
+            }),
+        }
+    }
+
+    pub fn set_size(&self, size: Size<i32, Logical>) {
+        match self.0.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => {
+                toplevel.with_pending_state(|state| state.size = Some(size));
+            }
+        }
+    }
+
+    pub fn set_fullscreen(&self, size: Size<i32, Logical>) {
+        match self.0.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => {
+                toplevel.with_pending_state(|state| {
+                    state.states.set(xdg_toplevel::State::Fullscreen);
+                    state.size = Some(size);
+                });
+            }
+        }
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        match self.0.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => toplevel
+                .current_state()
+                .states
+                .contains(xdg_toplevel::State::Fullscreen),
+        }
+    }
+
+    pub fn is_floating(&self) -> bool {
+        self.window_state().is_floating
+    }
+
+    /// Apply the parts of a window rule that may change while the window is mapped
+    pub fn apply_appearance_rule(&self, rule: &WindowRule) {
+        let mut state = self.window_state();
+        state.has_border = !rule.no_border;
+        state.opacity = rule.opacity.unwrap_or(1.0).clamp(0.0, 1.0);
+    }
+
+    pub fn app_id(&self) -> Option<String> {
+        self.window_state().app_id.clone()
+    }
+
+    pub fn title(&self) -> Option<String> {
+        self.window_state().title.clone()
+    }
+
+    /// Sync the app_id and title stored in the window state with the ones
+    /// last set by the client, returning whether any of them changed.
+    pub fn update_metadata(&self) -> bool {
+        let (app_id, title) = match self.0.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => with_states(toplevel.wl_surface(), |states| {
+                let attributes = states
+                    .data_map
+                    .get::<XdgToplevelSurfaceData>()
+                    .unwrap()
+                    .lock()
+                    .unwrap();
+                (attributes.app_id.clone(), attributes.title.clone())
+            }),
+        };
+
+        let mut state = self.window_state();
+        if state.app_id == app_id && state.title == title {
+            return false;
+        }
+
+        state.app_id = app_id;
+        state.title = title;
+        true
+    }
+
     pub fn send_frame<T, F>(
         &self,
         output: &Output,
@@ -115,6 +233,13 @@ impl WindowElement {
             .unwrap_or(false)
     }
 
+    /// Ask the client to close the window
+    pub fn close(&self) {
+        match self.0.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => toplevel.send_close(),
+        }
+    }
+
     pub fn send_pending_configure(&self) {
         match self.0.underlying_surface() {
             WindowSurface::Wayland(toplevel) => {
@@ -142,14 +267,18 @@ impl IsAlive for WindowElement {
 impl SpaceElement for WindowElement {
     fn geometry(&self) -> Rectangle<i32, Logical> {
         let mut geometry = SpaceElement::geometry(&self.0);
-        let border_size = CONFIG.border_thickness * 2;
-        geometry.size += (border_size, border_size).into();
+        if self.window_state().has_border {
+            let border_size = CONFIG.border_thickness * 2;
+            geometry.size += (border_size, border_size).into();
+        }
         geometry
     }
     fn bbox(&self) -> Rectangle<i32, Logical> {
         let mut bounding_box = SpaceElement::bbox(&self.0);
-        let border_size = CONFIG.border_thickness * 2;
-        bounding_box.size += (border_size, border_size).into();
+        if self.window_state().has_border {
+            let border_size = CONFIG.border_thickness * 2;
+            bounding_box.size += (border_size, border_size).into();
+        }
         bounding_box
     }
 
@@ -202,29 +331,38 @@ impl AsRenderElements<GlesRenderer> for WindowElement {
             return Vec::new();
         }
 
-        let border_thickness = CONFIG.border_thickness;
+        let (has_border, is_focused, opacity) = {
+            let state = self.window_state();
+            (state.has_border, state.is_focused, state.opacity)
+        };
 
-        let border_geometry = {
-            let window_geometry = SpaceElement::geometry(&self.0);
-            let loc: Point<i32, Logical> =
-                (location.x - border_thickness, location.y - border_thickness).into();
-            let size = window_geometry.size + (border_thickness * 2, border_thickness * 2).into();
+        let mut vec: Vec<WindowRenderElement> = Vec::new();
 
-            Rectangle::new(loc, size)
-        };
+        if has_border {
+            let border_thickness = CONFIG.border_thickness;
 
-        let color = {
-            let state = self.window_state();
-            if state.is_focused {
+            let border_geometry = {
+                let window_geometry = SpaceElement::geometry(&self.0);
+                let loc: Point<i32, Logical> =
+                    (location.x - border_thickness, location.y - border_thickness).into();
+                let size =
+                    window_geometry.size + (border_thickness * 2, border_thickness * 2).into();
+
+                Rectangle::new(loc, size)
+            };
+
+            let color = if is_focused {
                 CONFIG.border_color_focused
             } else {
                 CONFIG.border_color_unfocused
-            }
-        };
+            };
 
-        let border = BorderShader::element(renderer, border_geometry, color, border_thickness);
+            vec.push(
+                BorderShader::element(renderer, border_geometry, color, border_thickness).into(),
+            );
+        }
 
-        let mut vec: Vec<WindowRenderElement> = vec![border.into()];
+        let alpha = alpha * opacity;
 
         let window_elements =
             AsRenderElements::render_elements(&self.0, renderer, location, scale, alpha);
@@ -235,18 +373,68 @@ impl AsRenderElements<GlesRenderer> for WindowElement {
     }
 }
 
+impl WindowElement {
+    /// Surface of the window that receives pointer events other than motion
+    fn pointer_surface(&self) -> Option<WlSurface> {
+        self.window_state()
+            .pointer_surface
+            .as_ref()
+            .map(|(surface, _)| surface.clone())
+    }
+
+    /// Send pointer motion to the surface of the window under the pointer, moving
+    /// pointer focus between its surfaces as needed. The location of the event is
+    /// relative to the window
+    fn route_pointer_motion<BackendData: Backend>(
+        &self,
+        seat: &Seat<WallyState<BackendData>>,
+        data: &mut WallyState<BackendData>,
+        event: &MotionEvent,
+    ) {
+        let under = self.0.surface_under(event.location, WindowSurfaceType::ALL);
+        let previous = std::mem::replace(&mut self.window_state().pointer_surface, under.clone());
+
+        let Some((surface, location)) = under else {
+            if let Some((previous, _)) = previous {
+                PointerTarget::leave(&previous, seat, data, event.serial, event.time);
+            }
+            return;
+        };
+
+        let event = MotionEvent {
+            location: event.location - location.to_f64(),
+            serial: event.serial,
+            time: event.time,
+        };
+
+        match previous {
+            Some((previous, _)) if previous == surface => {
+                PointerTarget::motion(&surface, seat, data, &event);
+            }
+            previous => {
+                if let Some((previous, _)) = previous {
+                    PointerTarget::leave(&previous, seat, data, event.serial, event.time);
+                }
+                PointerTarget::enter(&surface, seat, data, &event);
+            }
+        }
+    }
+}
+
 impl<BackendData: Backend> PointerTarget<WallyState<BackendData>> for WindowElement {
     fn enter(
         &self,
         seat: &Seat<WallyState<BackendData>>,
         data: &mut WallyState<BackendData>,
-        _event: &MotionEvent,
+        event: &MotionEvent,
     ) {
         let serial = SERIAL_COUNTER.next_serial();
         let Some(surface) = self.wl_surface() else {
             return;
         };
 
+        self.route_pointer_motion(seat, data, event);
+
         let Some(keyboard) = seat.get_keyboard() else {
             return;
         };
@@ -266,100 +454,138 @@ impl<BackendData: Backend> PointerTarget<WallyState<BackendData>> for WindowElem
 
     fn motion(
         &self,
-        _seat: &Seat<WallyState<BackendData>>,
-        _data: &mut WallyState<BackendData>,
-        _event: &MotionEvent,
+        seat: &Seat<WallyState<BackendData>>,
+        data: &mut WallyState<BackendData>,
+        event: &MotionEvent,
     ) {
+        self.route_pointer_motion(seat, data, event);
     }
 
     fn relative_motion(
         &self,
-        _seat: &Seat<WallyState<BackendData>>,
-        _data: &mut WallyState<BackendData>,
-        _event: &RelativeMotionEvent,
+        seat: &Seat<WallyState<BackendData>>,
+        data: &mut WallyState<BackendData>,
+        event: &RelativeMotionEvent,
     ) {
+        if let Some(surface) = self.pointer_surface() {
+            PointerTarget::relative_motion(&surface, seat, data, event);
+        }
     }
 
     fn button(
         &self,
-        _seat: &Seat<WallyState<BackendData>>,
-        _data: &mut WallyState<BackendData>,
-        _event: &smithay::input::pointer::ButtonEvent,
+        seat: &Seat<WallyState<BackendData>>,
+        data: &mut WallyState<BackendData>,
+        event: &smithay::input::pointer::ButtonEvent,
     ) {
+        if let Some(surface) = self.pointer_surface() {
+            PointerTarget::button(&surface, seat, data, event);
+        }
     }
 
     fn axis(
         &self,
-        _seat: &Seat<WallyState<BackendData>>,
-        _data: &mut WallyState<BackendData>,
-        _frame: smithay::input::pointer::AxisFrame,
+        seat: &Seat<WallyState<BackendData>>,
+        data: &mut WallyState<BackendData>,
+        frame: smithay::input::pointer::AxisFrame,
     ) {
+        if let Some(surface) = self.pointer_surface() {
+            PointerTarget::axis(&surface, seat, data, frame);
+        }
     }
 
-    fn frame(&self, _seat: &Seat<WallyState<BackendData>>, _data: &mut WallyState<BackendData>) {}
+    fn frame(&self, seat: &Seat<WallyState<BackendData>>, data: &mut WallyState<BackendData>) {
+        if let Some(surface) = self.pointer_surface() {
+            PointerTarget::frame(&surface, seat, data);
+        }
+    }
 
     fn gesture_swipe_begin(
         &self,
-        _seat: &Seat<WallyState<BackendData>>,
-        _data: &mut WallyState<BackendData>,
-        _event: &GestureSwipeBeginEvent,
+        seat: &Seat<WallyState<BackendData>>,
+        data: &mut WallyState<BackendData>,
+        event: &GestureSwipeBeginEvent,
     ) {
+        if let Some(surface) = self.pointer_surface() {
+            PointerTarget::gesture_swipe_begin(&surface, seat, data, event);
+        }
     }
 
     fn gesture_swipe_update(
         &self,
-        _seat: &Seat<WallyState<BackendData>>,
-        _data: &mut WallyState<BackendData>,
-        _event: &GestureSwipeUpdateEvent,
+        seat: &Seat<WallyState<BackendData>>,
+        data: &mut WallyState<BackendData>,
+        event: &GestureSwipeUpdateEvent,
     ) {
+        if let Some(surface) = self.pointer_surface() {
+            PointerTarget::gesture_swipe_update(&surface, seat, data, event);
+        }
     }
 
     fn gesture_swipe_end(
         &self,
-        _seat: &Seat<WallyState<BackendData>>,
-        _data: &mut WallyState<BackendData>,
-        _event: &GestureSwipeEndEvent,
+        seat: &Seat<WallyState<BackendData>>,
+        data: &mut WallyState<BackendData>,
+        event: &GestureSwipeEndEvent,
     ) {
+        if let Some(surface) = self.pointer_surface() {
+            PointerTarget::gesture_swipe_end(&surface, seat, data, event);
+        }
     }
 
     fn gesture_pinch_begin(
         &self,
-        _seat: &Seat<WallyState<BackendData>>,
-        _data: &mut WallyState<BackendData>,
-        _event: &GesturePinchBeginEvent,
+        seat: &Seat<WallyState<BackendData>>,
+        data: &mut WallyState<BackendData>,
+        event: &GesturePinchBeginEvent,
     ) {
+        if let Some(surface) = self.pointer_surface() {
+            PointerTarget::gesture_pinch_begin(&surface, seat, data, event);
+        }
     }
 
     fn gesture_pinch_update(
         &self,
-        _seat: &Seat<WallyState<BackendData>>,
-        _data: &mut WallyState<BackendData>,
-        _event: &GesturePinchUpdateEvent,
+        seat: &Seat<WallyState<BackendData>>,
+        data: &mut WallyState<BackendData>,
+        event: &GesturePinchUpdateEvent,
     ) {
+        if let Some(surface) = self.pointer_surface() {
+            PointerTarget::gesture_pinch_update(&surface, seat, data, event);
+        }
     }
 
     fn gesture_pinch_end(
         &self,
-        _seat: &Seat<WallyState<BackendData>>,
-        _data: &mut WallyState<BackendData>,
-        _event: &GesturePinchEndEvent,
+        seat: &Seat<WallyState<BackendData>>,
+        data: &mut WallyState<BackendData>,
+        event: &GesturePinchEndEvent,
     ) {
+        if let Some(surface) = self.pointer_surface() {
+            PointerTarget::gesture_pinch_end(&surface, seat, data, event);
+        }
     }
 
     fn gesture_hold_begin(
         &self,
-        _seat: &Seat<WallyState<BackendData>>,
-        _data: &mut WallyState<BackendData>,
-        _event: &GestureHoldBeginEvent,
+        seat: &Seat<WallyState<BackendData>>,
+        data: &mut WallyState<BackendData>,
+        event: &GestureHoldBeginEvent,
     ) {
+        if let Some(surface) = self.pointer_surface() {
+            PointerTarget::gesture_hold_begin(&surface, seat, data, event);
+        }
     }
 
     fn gesture_hold_end(
         &self,
-        _seat: &Seat<WallyState<BackendData>>,
-        _data: &mut WallyState<BackendData>,
-        _event: &GestureHoldEndEvent,
+        seat: &Seat<WallyState<BackendData>>,
+        data: &mut WallyState<BackendData>,
+        event: &GestureHoldEndEvent,
     ) {
+        if let Some(surface) = self.pointer_surface() {
+            PointerTarget::gesture_hold_end(&surface, seat, data, event);
+        }
     }
 
     fn leave(
@@ -367,8 +593,13 @@ impl<BackendData: Backend> PointerTarget<WallyState<BackendData>> for WindowElem
         seat: &Seat<WallyState<BackendData>>,
         data: &mut WallyState<BackendData>,
         serial: Serial,
-        _time: u32,
+        time: u32,
     ) {
+        let pointer_surface = self.window_state().pointer_surface.take();
+        if let Some((surface, _)) = pointer_surface {
+            PointerTarget::leave(&surface, seat, data, serial, time);
+        }
+
         let Some(keyboard) = seat.get_keyboard() else {
             return;
         };
@@ -0,0 +1,268 @@
+use std::time::Instant;
+
+use smithay::{
+    input::pointer::{GestureSwipeBeginEvent, GestureSwipeEndEvent, GestureSwipeUpdateEvent},
+    utils::{Logical, Point, SERIAL_COUNTER},
+};
+
+use crate::{
+    backend::Backend,
+    config::CONFIG,
+    state::WallyState,
+    types::{
+        gesture::{GestureAction, SwipeDirection, SwipeGesture},
+        keybind::Action,
+    },
+};
+
+/// Distance the fingers travel before the direction of a swipe is decided
+const DIRECTION_THRESHOLD: f64 = 16.0;
+/// Distance a swipe has to travel to trigger its action
+const ACTION_THRESHOLD: f64 = 100.0;
+/// Touchpad distance corresponding to swiping across a whole output
+const WORKSPACE_SWIPE_DISTANCE: f64 = 600.0;
+/// Progress past which lifting the fingers switches to the neighbouring workspace
+const WORKSPACE_SWIPE_THRESHOLD: f64 = 0.25;
+/// Output widths per second the workspaces move once the fingers are lifted
+const WORKSPACE_SWIPE_SPEED: f64 = 4.0;
+
+/// A swipe intercepted by the compositor
+#[derive(Debug)]
+pub enum Swipe {
+    /// The fingers are down, but they have not moved far enough to decide the direction
+    Pending {
+        fingers: u32,
+        delta: Point<f64, Logical>,
+    },
+    Workspace(SwipeDirection),
+    Action {
+        action: Action,
+        direction: SwipeDirection,
+        distance: f64,
+    },
+    /// No compositor gesture matches, the swipe is passed on to clients
+    Forwarded,
+}
+
+#[derive(Debug)]
+pub struct WorkspaceSwipe {
+    monitor: usize,
+    /// Output widths the active workspace is moved by, towards the next workspace if positive
+    progress: f64,
+    /// Progress the workspaces move to once the fingers have been lifted
+    target: Option<f64>,
+    last_frame: Instant,
+}
+
+fn along(delta: Point<f64, Logical>, direction: SwipeDirection) -> f64 {
+    match direction {
+        SwipeDirection::Horizontal => delta.x,
+        SwipeDirection::Vertical => delta.y,
+    }
+}
+
+impl<BackendData: Backend> WallyState<BackendData> {
+    /// Whether a swipe with this many fingers could be a compositor gesture
+    pub fn intercepts_swipe(&self, fingers: u32) -> bool {
+        !self.is_locked()
+            && CONFIG
+                .swipe_gestures
+                .keys()
+                .any(|gesture| gesture.fingers == fingers)
+    }
+
+    pub fn begin_swipe(&mut self, fingers: u32) {
+        self.swipe = Some(Swipe::Pending {
+            fingers,
+            delta: Point::default(),
+        });
+    }
+
+    pub fn update_swipe(&mut self, delta: Point<f64, Logical>, time: u32) {
+        match self.swipe.as_mut() {
+            Some(Swipe::Pending {
+                fingers,
+                delta: total,
+            }) => {
+                *total += delta;
+                if total.x.hypot(total.y) < DIRECTION_THRESHOLD {
+                    return;
+                }
+
+                let direction = if total.x.abs() >= total.y.abs() {
+                    SwipeDirection::Horizontal
+                } else {
+                    SwipeDirection::Vertical
+                };
+                let total = *total;
+                let fingers = *fingers;
+
+                let gesture = SwipeGesture::new(fingers, direction);
+                self.swipe = match CONFIG.swipe_gestures.get(&gesture) {
+                    Some(GestureAction::SwitchWorkspace) if self.begin_workspace_swipe() => {
+                        self.move_workspace_swipe(along(total, direction));
+                        Some(Swipe::Workspace(direction))
+                    }
+                    Some(GestureAction::Action(action)) => Some(Swipe::Action {
+                        action: action.clone(),
+                        direction,
+                        distance: along(total, direction),
+                    }),
+                    _ => {
+                        self.forward_swipe(fingers, total, time);
+                        Some(Swipe::Forwarded)
+                    }
+                };
+            }
+            Some(Swipe::Workspace(direction)) => {
+                let distance = along(delta, *direction);
+                self.move_workspace_swipe(distance);
+            }
+            Some(Swipe::Action {
+                direction,
+                distance,
+                ..
+            }) => *distance += along(delta, *direction),
+            Some(Swipe::Forwarded) => {
+                let pointer = self.pointer.clone();
+                pointer.gesture_swipe_update(self, &GestureSwipeUpdateEvent { time, delta });
+            }
+            None => {}
+        }
+    }
+
+    pub fn end_swipe(&mut self, cancelled: bool, time: u32) {
+        match self.swipe.take() {
+            Some(Swipe::Workspace(_)) => {
+                let Some(workspace_swipe) = self.workspace_swipe.as_mut() else {
+                    return;
+                };
+
+                let progress = workspace_swipe.progress;
+                let target = if !cancelled && progress.abs() > WORKSPACE_SWIPE_THRESHOLD {
+                    progress.signum()
+                } else {
+                    0.0
+                };
+
+                workspace_swipe.target = Some(target);
+                workspace_swipe.last_frame = Instant::now();
+            }
+            Some(Swipe::Action {
+                action, distance, ..
+            }) if !cancelled && distance.abs() > ACTION_THRESHOLD => {
+                self.handle_action(action);
+            }
+            Some(Swipe::Forwarded) => {
+                let pointer = self.pointer.clone();
+                pointer.gesture_swipe_end(
+                    self,
+                    &GestureSwipeEndEvent {
+                        serial: SERIAL_COUNTER.next_serial(),
+                        time,
+                        cancelled,
+                    },
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// Start the client gesture late, with the motion held back while the direction was undecided
+    fn forward_swipe(&mut self, fingers: u32, delta: Point<f64, Logical>, time: u32) {
+        let pointer = self.pointer.clone();
+        pointer.gesture_swipe_begin(
+            self,
+            &GestureSwipeBeginEvent {
+                serial: SERIAL_COUNTER.next_serial(),
+                time,
+                fingers,
+            },
+        );
+        pointer.gesture_swipe_update(self, &GestureSwipeUpdateEvent { time, delta });
+    }
+
+    /// Start moving the workspaces of the active monitor, returns false if there is nothing to swipe to
+    fn begin_workspace_swipe(&mut self) -> bool {
+        // a previous swipe might still be animating
+        self.finish_workspace_swipe();
+
+        let Some(monitor) = self.active_monitor_index() else {
+            return false;
+        };
+
+        if self.monitors[monitor].workspace_count() < 2 {
+            return false;
+        }
+
+        self.monitors[monitor].begin_swipe(&mut self.space);
+        self.workspace_swipe = Some(WorkspaceSwipe {
+            monitor,
+            progress: 0.0,
+            target: None,
+            last_frame: Instant::now(),
+        });
+
+        true
+    }
+
+    fn move_workspace_swipe(&mut self, distance: f64) {
+        let Some(workspace_swipe) = self.workspace_swipe.as_mut() else {
+            return;
+        };
+
+        // swiping to the left reveals the workspace to the right
+        workspace_swipe.progress =
+            (workspace_swipe.progress - distance / WORKSPACE_SWIPE_DISTANCE).clamp(-1.0, 1.0);
+
+        self.monitors[workspace_swipe.monitor]
+            .show_swipe(workspace_swipe.progress, &mut self.space);
+    }
+
+    /// Move the workspaces towards their target once the fingers have been lifted, called every frame
+    pub fn animate_workspace_swipe(&mut self) {
+        let Some(workspace_swipe) = self.workspace_swipe.as_mut() else {
+            return;
+        };
+        let Some(target) = workspace_swipe.target else {
+            return;
+        };
+
+        let now = Instant::now();
+        let step =
+            now.duration_since(workspace_swipe.last_frame).as_secs_f64() * WORKSPACE_SWIPE_SPEED;
+        workspace_swipe.last_frame = now;
+
+        let remaining = target - workspace_swipe.progress;
+        if remaining.abs() <= step {
+            self.finish_workspace_swipe();
+            return;
+        }
+
+        workspace_swipe.progress += step.copysign(remaining);
+        self.monitors[workspace_swipe.monitor]
+            .show_swipe(workspace_swipe.progress, &mut self.space);
+    }
+
+    /// Jump to the end of a workspace swipe
+    pub fn finish_workspace_swipe(&mut self) {
+        let Some(workspace_swipe) = self.workspace_swipe.take() else {
+            return;
+        };
+
+        let monitor = &mut self.monitors[workspace_swipe.monitor];
+        let index = match workspace_swipe.target {
+            Some(target) if target != 0.0 => monitor.swipe_neighbour(target),
+            _ => monitor.active_workspace_index(),
+        };
+        let switched = index != monitor.active_workspace_index();
+
+        monitor.end_swipe(index, &mut self.space);
+
+        if switched {
+            // the focused window might have been hidden along with the previous workspace
+            let keyboard = self.seat.get_keyboard().unwrap();
+            keyboard.set_focus(self, None, SERIAL_COUNTER.next_serial());
+        }
+    }
+}
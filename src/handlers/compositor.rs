@@ -34,12 +34,25 @@ impl<BackendData: Backend> CompositorHandler for WallyState<BackendData> {
             while let Some(parent) = get_parent(&root) {
                 root = parent;
             }
-            if let Some(window) = self.space.elements().find(|w| w.surface_matches(&root)) {
+            if let Some(window) = self.window_for_surface(&root) {
                 window.on_commit();
+
+                if window.update_metadata() {
+                    self.window_metadata_changed(&window);
+                }
+
+                if !window.initial_configure_sent() {
+                    self.apply_window_rules(&window);
+                    window.send_configure();
+                }
+
+                if self.is_scratchpad(&window) {
+                    self.refresh_scratchpad(&window);
+                }
             }
         };
 
-        xdg_shell::handle_commit(&mut self.popups, &self.space, surface);
+        xdg_shell::handle_commit(self, surface);
     }
 }
 
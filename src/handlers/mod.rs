@@ -1,24 +1,62 @@
 mod compositor;
 mod xdg_shell;
 
+use std::os::fd::OwnedFd;
+
 use crate::backend::Backend;
+use crate::clipboard::{self, ClipboardContents};
+use crate::config::CONFIG;
 use crate::focus::PointerFocusTarget;
+use crate::ipc::IpcMessage;
+use crate::libinput;
 use crate::WallyState;
 
 //
 // Wl Seat
 //
 
+use smithay::backend::input::TabletToolDescriptor;
+use smithay::desktop::{space::SpaceElement, PopupKind, PopupManager};
+use smithay::input::keyboard::LedState;
+use smithay::input::pointer::CursorImageStatus;
+use smithay::input::pointer::PointerHandle;
 use smithay::input::{Seat, SeatHandler, SeatState};
+use smithay::output::Output;
+use smithay::reexports::wayland_server::protocol::wl_output::WlOutput;
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::reexports::wayland_server::Resource;
+use smithay::utils::{IsAlive, Logical, Point, Rectangle, SERIAL_COUNTER};
+use smithay::wayland::idle_inhibit::IdleInhibitHandler;
+use smithay::wayland::idle_notify::{IdleNotifierHandler, IdleNotifierState};
+use smithay::wayland::input_method::{self, InputMethodHandler};
+use smithay::wayland::keyboard_shortcuts_inhibit::{
+    KeyboardShortcutsInhibitHandler, KeyboardShortcutsInhibitState, KeyboardShortcutsInhibitor,
+};
 use smithay::wayland::output::OutputHandler;
+use smithay::wayland::pointer_constraints::{with_pointer_constraint, PointerConstraintsHandler};
+use smithay::wayland::seat::WaylandFocus;
 use smithay::wayland::selection::data_device::{
     set_data_device_focus, ClientDndGrabHandler, DataDeviceHandler, DataDeviceState,
     ServerDndGrabHandler,
 };
-use smithay::wayland::selection::SelectionHandler;
-use smithay::{delegate_data_device, delegate_output, delegate_seat};
+use smithay::wayland::selection::primary_selection::{
+    set_primary_focus, PrimarySelectionHandler, PrimarySelectionState,
+};
+use smithay::wayland::selection::wlr_data_control::{DataControlHandler, DataControlState};
+use smithay::wayland::selection::{SelectionHandler, SelectionSource, SelectionTarget};
+use smithay::wayland::session_lock::{
+    LockSurface, SessionLockHandler, SessionLockManagerState, SessionLocker,
+};
+use smithay::wayland::tablet_manager::TabletSeatHandler;
+use smithay::{
+    delegate_cursor_shape, delegate_data_control, delegate_data_device, delegate_idle_inhibit,
+    delegate_idle_notify, delegate_input_method_manager, delegate_keyboard_shortcuts_inhibit,
+    delegate_output, delegate_pointer_constraints, delegate_pointer_gestures,
+    delegate_primary_selection, delegate_relative_pointer, delegate_seat, delegate_session_lock,
+    delegate_tablet_manager, delegate_text_input_manager,
+};
+
+use crate::lock::LockState;
 
 impl<BackendData: Backend> SeatHandler for WallyState<BackendData> {
     type KeyboardFocus = WlSurface;
@@ -40,18 +78,74 @@ impl<BackendData: Backend> SeatHandler for WallyState<BackendData> {
     fn focus_changed(&mut self, seat: &Seat<Self>, focused: Option<&WlSurface>) {
         let dh = &self.display_handle;
         let client = focused.and_then(|s| dh.get_client(s.id()).ok());
-        set_data_device_focus(dh, seat, client);
+        set_data_device_focus(dh, seat, client.clone());
+        set_primary_focus(dh, seat, client);
+    }
+
+    fn led_state_changed(&mut self, _seat: &Seat<Self>, led_state: LedState) {
+        self.led_state = led_state;
+        libinput::update_leds(&mut self.libinput_keyboards, led_state);
+        self.backend_data.update_led_state(led_state);
+        self.ipc_broadcast(IpcMessage::from(led_state));
     }
 }
 
 delegate_seat!(@<BackendData: Backend + 'static> WallyState<BackendData>);
 
+//
+// Cursor Shape
+//
+
+// cursor shapes requested by clients end up in `SeatHandler::cursor_image`
+// as named cursors, which are rendered from the loaded cursor theme
+delegate_cursor_shape!(@<BackendData: Backend + 'static> WallyState<BackendData>);
+
+//
+// Tablet Manager
+//
+
+impl<BackendData: Backend> TabletSeatHandler for WallyState<BackendData> {
+    fn tablet_tool_image(&mut self, _tool: &TabletToolDescriptor, image: CursorImageStatus) {
+        // tablet tools move the pointer, so their cursor is drawn in place of the pointer's
+        self.tablet_cursor_status = Some(image);
+    }
+}
+
+delegate_tablet_manager!(@<BackendData: Backend + 'static> WallyState<BackendData>);
+
 //
 // Wl Data Device
 //
 
 impl<BackendData: Backend> SelectionHandler for WallyState<BackendData> {
-    type SelectionUserData = ();
+    type SelectionUserData = ClipboardContents;
+
+    fn new_selection(
+        &mut self,
+        ty: SelectionTarget,
+        source: Option<SelectionSource>,
+        seat: Seat<Self>,
+    ) {
+        if !CONFIG.clipboard_keeper || !matches!(ty, SelectionTarget::Clipboard) {
+            return;
+        }
+
+        match source {
+            Some(source) => self.keep_clipboard(source.mime_types(), seat),
+            None => self.clipboard_cleared(seat),
+        }
+    }
+
+    fn send_selection(
+        &mut self,
+        _ty: SelectionTarget,
+        mime_type: String,
+        fd: OwnedFd,
+        _seat: Seat<Self>,
+        user_data: &ClipboardContents,
+    ) {
+        clipboard::send_clipboard(user_data, mime_type, fd);
+    }
 }
 
 impl<BackendData: Backend> DataDeviceHandler for WallyState<BackendData> {
@@ -65,9 +159,229 @@ impl<BackendData: Backend> ServerDndGrabHandler for WallyState<BackendData> {}
 
 delegate_data_device!(@<BackendData: Backend + 'static> WallyState<BackendData>);
 
+//
+// Primary Selection
+//
+
+impl<BackendData: Backend> PrimarySelectionHandler for WallyState<BackendData> {
+    fn primary_selection_state(&self) -> &PrimarySelectionState {
+        &self.primary_selection_state
+    }
+}
+
+delegate_primary_selection!(@<BackendData: Backend + 'static> WallyState<BackendData>);
+
+//
+// Wlr Data Control
+//
+
+impl<BackendData: Backend> DataControlHandler for WallyState<BackendData> {
+    fn data_control_state(&self) -> &DataControlState {
+        &self.data_control_state
+    }
+}
+
+delegate_data_control!(@<BackendData: Backend + 'static> WallyState<BackendData>);
+
 //
 // Wl Output & Xdg Output
 //
 
 impl<BackendData: Backend> OutputHandler for WallyState<BackendData> {}
 delegate_output!(@<BackendData: Backend + 'static> WallyState<BackendData>);
+
+//
+// Session Lock
+//
+
+impl<BackendData: Backend> SessionLockHandler for WallyState<BackendData> {
+    fn lock_state(&mut self) -> &mut SessionLockManagerState {
+        &mut self.session_lock_manager_state
+    }
+
+    fn lock(&mut self, confirmation: SessionLocker) {
+        match self.lock_state {
+            LockState::Unlocked => {
+                // outputs are blanked from the next frame on, the lock
+                // is confirmed once that frame has been rendered
+                self.lock_state = LockState::Locking(confirmation);
+            }
+            // a new locker may only take over if the previous one is gone,
+            // the outputs are already blanked so the lock can be confirmed right away
+            LockState::Locked
+                if !self.monitors.iter().any(|monitor| {
+                    monitor
+                        .lock_surface()
+                        .is_some_and(|surface| surface.alive())
+                }) =>
+            {
+                confirmation.lock();
+            }
+            // dropping the locker tells the client that locking failed
+            _ => return,
+        }
+
+        for monitor in &mut self.monitors {
+            monitor.set_lock_surface(None);
+        }
+
+        let keyboard = self.seat.get_keyboard().unwrap();
+        keyboard.set_focus(self, None, SERIAL_COUNTER.next_serial());
+        self.refresh_pointer_focus();
+    }
+
+    fn unlock(&mut self) {
+        self.lock_state = LockState::Unlocked;
+
+        for monitor in &mut self.monitors {
+            monitor.set_lock_surface(None);
+        }
+
+        let keyboard = self.seat.get_keyboard().unwrap();
+        keyboard.set_focus(self, None, SERIAL_COUNTER.next_serial());
+        self.refresh_pointer_focus();
+    }
+
+    fn new_surface(&mut self, surface: LockSurface, output: WlOutput) {
+        let Some(output) = Output::from_resource(&output) else {
+            return;
+        };
+
+        let Some(monitor) = self
+            .monitors
+            .iter_mut()
+            .find(|monitor| monitor.output_ref() == &output)
+        else {
+            return;
+        };
+
+        monitor.set_lock_surface(Some(surface));
+
+        self.configure_lock_surfaces();
+        self.focus_lock_surface();
+        self.refresh_pointer_focus();
+    }
+}
+
+delegate_session_lock!(@<BackendData: Backend + 'static> WallyState<BackendData>);
+
+//
+// Idle Notify & Idle Inhibit
+//
+
+impl<BackendData: Backend> IdleNotifierHandler for WallyState<BackendData> {
+    fn idle_notifier_state(&mut self) -> &mut IdleNotifierState<Self> {
+        &mut self.idle_notifier_state
+    }
+}
+
+delegate_idle_notify!(@<BackendData: Backend + 'static> WallyState<BackendData>);
+
+impl<BackendData: Backend> IdleInhibitHandler for WallyState<BackendData> {
+    fn inhibit(&mut self, surface: WlSurface) {
+        self.idle_inhibitors.insert(surface);
+        self.refresh_idle_inhibit();
+    }
+
+    fn uninhibit(&mut self, surface: WlSurface) {
+        self.idle_inhibitors.remove(&surface);
+        self.refresh_idle_inhibit();
+    }
+}
+
+delegate_idle_inhibit!(@<BackendData: Backend + 'static> WallyState<BackendData>);
+
+//
+// Pointer Constraints & Relative Pointer
+//
+
+impl<BackendData: Backend> PointerConstraintsHandler for WallyState<BackendData> {
+    fn new_constraint(&mut self, surface: &WlSurface, pointer: &PointerHandle<Self>) {
+        // constraints of the surface under the pointer apply right away,
+        // others once the pointer enters their surface
+        let Some(focus) = pointer.current_focus() else {
+            return;
+        };
+
+        if focus.wl_surface().as_deref() == Some(surface) {
+            with_pointer_constraint(surface, pointer, |constraint| {
+                if let Some(constraint) = constraint {
+                    constraint.activate();
+                }
+            });
+        }
+    }
+
+    fn cursor_position_hint(
+        &mut self,
+        surface: &WlSurface,
+        _pointer: &PointerHandle<Self>,
+        location: Point<f64, Logical>,
+    ) {
+        // only used once the lock is released
+        self.cursor_position_hint = Some((surface.clone(), location));
+    }
+}
+
+delegate_pointer_constraints!(@<BackendData: Backend + 'static> WallyState<BackendData>);
+delegate_relative_pointer!(@<BackendData: Backend + 'static> WallyState<BackendData>);
+
+//
+// Pointer Gestures
+//
+
+delegate_pointer_gestures!(@<BackendData: Backend + 'static> WallyState<BackendData>);
+
+//
+// Keyboard Shortcuts Inhibit
+//
+
+impl<BackendData: Backend> KeyboardShortcutsInhibitHandler for WallyState<BackendData> {
+    fn keyboard_shortcuts_inhibit_state(&mut self) -> &mut KeyboardShortcutsInhibitState {
+        &mut self.keyboard_shortcuts_inhibit_state
+    }
+
+    fn new_inhibitor(&mut self, inhibitor: KeyboardShortcutsInhibitor) {
+        // the escape binding lets the user take the shortcuts back
+        inhibitor.activate();
+    }
+}
+
+delegate_keyboard_shortcuts_inhibit!(@<BackendData: Backend + 'static> WallyState<BackendData>);
+
+//
+// Input Method & Text Input
+//
+
+impl<BackendData: Backend> InputMethodHandler for WallyState<BackendData> {
+    fn new_popup(&mut self, surface: input_method::PopupSurface) {
+        if let Err(err) = self.popups.track_popup(PopupKind::from(surface)) {
+            tracing::warn!("Failed to track input method popup: {err}");
+        }
+    }
+
+    fn popup_repositioned(&mut self, surface: input_method::PopupSurface) {
+        self.unconstrain_input_method_popup(&surface);
+    }
+
+    fn dismiss_popup(&mut self, surface: input_method::PopupSurface) {
+        if let Some(parent) = surface.get_parent().map(|parent| parent.surface.clone()) {
+            let _ = PopupManager::dismiss_popup(&parent, &PopupKind::from(surface));
+        }
+    }
+
+    /// Popups are placed at the text cursor, which is relative to the text input surface, so the
+    /// window geometry is given as seen from that surface
+    fn parent_geometry(&self, parent: &WlSurface) -> Rectangle<i32, Logical> {
+        self.text_input_window(parent)
+            .map(|(window, offset)| {
+                let mut geometry = SpaceElement::geometry(&window.0);
+                geometry.loc = (-offset.x, -offset.y).into();
+                geometry
+            })
+            .unwrap_or_default()
+    }
+}
+
+delegate_input_method_manager!(@<BackendData: Backend + 'static> WallyState<BackendData>);
+delegate_text_input_manager!(@<BackendData: Backend + 'static> WallyState<BackendData>);
@@ -1,7 +1,8 @@
 use smithay::{
     delegate_xdg_decoration, delegate_xdg_shell,
     desktop::{
-        find_popup_root_surface, get_popup_toplevel_coords, PopupKind, PopupManager, Space, Window,
+        find_popup_root_surface, get_popup_toplevel_coords, space::SpaceElement,
+        utils::bbox_from_surface_tree, PopupKind, PopupManager, Window,
     },
     reexports::{
         wayland_protocols::xdg::{
@@ -9,17 +10,21 @@ use smithay::{
         },
         wayland_server::protocol::{wl_seat, wl_surface::WlSurface},
     },
-    utils::Serial,
+    utils::{IsAlive, Logical, Point, Serial},
     wayland::{
-        compositor::with_states,
+        compositor::{get_parent, with_states, SubsurfaceCachedState},
+        input_method,
         shell::xdg::{
             decoration::XdgDecorationHandler, PopupSurface, PositionerState, ToplevelSurface,
-            XdgShellHandler, XdgShellState, XdgToplevelSurfaceData,
+            XdgShellHandler, XdgShellState,
         },
     },
 };
 
-use crate::{backend::Backend, elements::window::WindowElement, WallyState};
+use crate::{
+    backend::Backend, config::CONFIG, elements::window::WindowElement,
+    types::window_rule::WindowRule, WallyState,
+};
 
 impl<BackendData: Backend> XdgShellHandler for WallyState<BackendData> {
     fn xdg_shell_state(&mut self) -> &mut XdgShellState {
@@ -39,7 +44,28 @@ impl<BackendData: Backend> XdgShellHandler for WallyState<BackendData> {
             [x as i32, y as i32]
         };
 
-        self.space.map_element(window, (x, y), true);
+        let Some(monitor_index) = self.active_monitor_index() else {
+            return;
+        };
+
+        self.space.map_element(window.clone(), (x, y), true);
+        self.monitors[monitor_index]
+            .active_workspace_mut()
+            .add_window(window, (x, y).into());
+    }
+
+    fn toplevel_destroyed(&mut self, surface: ToplevelSurface) {
+        let Some(window) = self.window_for_surface(surface.wl_surface()) else {
+            return;
+        };
+
+        for monitor in self.monitors.iter_mut() {
+            monitor.remove_window(&window);
+        }
+        self.scratchpads
+            .retain(|_, scratchpad| *scratchpad != window);
+
+        self.space.unmap_elem(&window);
     }
 
     fn new_popup(&mut self, surface: PopupSurface, _positioner: PositionerState) {
@@ -83,31 +109,13 @@ impl<BackendData: Backend> XdgShellHandler for WallyState<BackendData> {
 delegate_xdg_shell!(@<BackendData: Backend + 'static> WallyState<BackendData>);
 
 /// Should be called on `WlSurface::commit`
-pub fn handle_commit(popups: &mut PopupManager, space: &Space<WindowElement>, surface: &WlSurface) {
-    // Handle toplevel commits.
-    if let Some(window) = space
-        .elements()
-        .find(|w| w.surface_matches(surface))
-        .cloned()
-    {
-        let initial_configure_sent = with_states(surface, |states| {
-            states
-                .data_map
-                .get::<XdgToplevelSurfaceData>()
-                .unwrap()
-                .lock()
-                .unwrap()
-                .initial_configure_sent
-        });
-
-        if !initial_configure_sent {
-            window.send_configure();
-        }
-    }
-
+pub fn handle_commit<BackendData: Backend>(
+    state: &mut WallyState<BackendData>,
+    surface: &WlSurface,
+) {
     // Handle popup commits.
-    popups.commit(surface);
-    if let Some(popup) = popups.find_popup(surface) {
+    state.popups.commit(surface);
+    if let Some(popup) = state.popups.find_popup(surface) {
         match popup {
             PopupKind::Xdg(ref xdg) => {
                 if !xdg.is_initial_configure_sent() {
@@ -116,17 +124,236 @@ pub fn handle_commit(popups: &mut PopupManager, space: &Space<WindowElement>, su
                     xdg.send_configure().expect("initial configure failed");
                 }
             }
-            PopupKind::InputMethod(ref _input_method) => {}
+            PopupKind::InputMethod(ref input_method) => {
+                // popups are placed at the text cursor of their parent, see
+                // `InputMethodHandler::parent_geometry`, a popup outliving it has nowhere to go
+                let parent = input_method
+                    .get_parent()
+                    .map(|parent| parent.surface.clone());
+                match parent {
+                    Some(parent) if !parent.alive() => {
+                        let _ = PopupManager::dismiss_popup(&parent, &popup);
+                    }
+                    _ => state.unconstrain_input_method_popup(input_method),
+                }
+            }
         }
     }
 }
 
 impl<BackendData: Backend> WallyState<BackendData> {
-    fn window_for_surface(&self, surface: &WlSurface) -> Option<WindowElement> {
-        self.space
-            .elements()
-            .find(|window| window.surface_matches(surface))
-            .cloned()
+    /// Resolve the window rules matching a window and place it accordingly.
+    ///
+    /// The app_id and title of a window are only known once the client commits
+    /// its initial state, so this should be called right before the initial configure.
+    pub fn apply_window_rules(&mut self, window: &WindowElement) {
+        let app_id = window.app_id();
+        let title = window.title();
+        let rule = WindowRule::resolve(&CONFIG.window_rules, app_id.as_deref(), title.as_deref());
+
+        tracing::debug!(?app_id, ?title, ?rule, "Applying window rules");
+
+        window.apply_appearance_rule(&rule);
+        {
+            let mut state = window.window_state();
+            state.is_floating = rule.floating;
+            if rule.no_focus {
+                state.is_focused = false;
+            }
+        }
+
+        if rule.no_focus {
+            window.set_activated(false);
+        }
+
+        if let Some(size) = rule.size {
+            window.set_size(size);
+        }
+
+        if let Some(name) = rule.scratchpad {
+            for monitor in self.monitors.iter_mut() {
+                monitor.remove_window(window);
+            }
+
+            window.window_state().is_floating = true;
+            self.pending_scratchpads.remove(&name);
+            self.scratchpads.insert(name, window.clone());
+            self.show_scratchpad(window);
+            return;
+        }
+
+        let Some((current_monitor, current_workspace)) =
+            self.monitors
+                .iter()
+                .enumerate()
+                .find_map(|(index, monitor)| {
+                    monitor
+                        .workspace_of(window)
+                        .map(|workspace| (index, workspace))
+                })
+        else {
+            return;
+        };
+
+        let monitor_index = rule
+            .monitor
+            .as_ref()
+            .and_then(|name| {
+                self.monitors
+                    .iter()
+                    .position(|monitor| monitor.output_ref().name() == *name)
+            })
+            .unwrap_or(current_monitor);
+
+        let workspace_index = rule.workspace_index(
+            self.monitors[monitor_index].workspace_count(),
+            if monitor_index == current_monitor {
+                current_workspace
+            } else {
+                self.monitors[monitor_index].active_workspace_index()
+            },
+        );
+
+        let current_geometry = self
+            .space
+            .output_geometry(self.monitors[current_monitor].output_ref())
+            .unwrap_or_default();
+        let output_geometry = self
+            .space
+            .output_geometry(self.monitors[monitor_index].output_ref())
+            .unwrap_or_default();
+
+        if rule.fullscreen {
+            window.set_fullscreen(output_geometry.size);
+        }
+
+        let stored_location = self.monitors[current_monitor]
+            .workspace_mut(current_workspace)
+            .and_then(|workspace| workspace.remove_window(window))
+            .unwrap_or_default();
+        let location = self
+            .space
+            .element_location(window)
+            .unwrap_or(stored_location);
+
+        let size = self
+            .space
+            .element_geometry(window)
+            .map(|geometry| geometry.size)
+            .unwrap_or_default();
+        let location = rule.placement(location, size, current_geometry, output_geometry);
+
+        let monitor = &mut self.monitors[monitor_index];
+        let is_visible = workspace_index == monitor.active_workspace_index();
+
+        monitor
+            .workspace_mut(workspace_index)
+            .unwrap()
+            .add_window(window.clone(), location);
+
+        if is_visible {
+            self.space
+                .map_element(window.clone(), location, !rule.no_focus);
+            self.raise_floating_windows();
+        } else {
+            self.space.unmap_elem(window);
+        }
+    }
+
+    /// The window a text input surface belongs to, with the offset of the surface from the window
+    /// geometry
+    ///
+    /// Text inputs aren't necessarily in the toplevel surface, subsurfaces and popups have them too.
+    pub fn text_input_window(
+        &self,
+        surface: &WlSurface,
+    ) -> Option<(WindowElement, Point<i32, Logical>)> {
+        let mut surface = surface.clone();
+        let mut offset = Point::default();
+
+        while let Some(parent) = get_parent(&surface) {
+            offset += with_states(&surface, |states| {
+                states
+                    .cached_state
+                    .get::<SubsurfaceCachedState>()
+                    .current()
+                    .location
+            });
+            surface = parent;
+        }
+
+        if let Some(window) = self.window_for_surface(&surface) {
+            return Some((window, offset - SpaceElement::geometry(&window.0).loc));
+        }
+
+        // popups are positioned relative to the window geometry already
+        let popup = self.popups.find_popup(&surface)?;
+        let root = find_popup_root_surface(&popup).ok()?;
+        let window = self.window_for_surface(&root)?;
+        offset += get_popup_toplevel_coords(&popup) - popup.geometry().loc;
+
+        Some((window, offset))
+    }
+
+    /// Keep an input method popup on the output of its text input
+    ///
+    /// The popup goes below the text cursor, above it when there is no room left below, and is
+    /// pushed back sideways when it runs past the output edges.
+    pub fn unconstrain_input_method_popup(&self, popup: &input_method::PopupSurface) {
+        let Some(parent) = popup.get_parent() else {
+            return;
+        };
+
+        let Some((window, _)) = self.text_input_window(&parent.surface) else {
+            return;
+        };
+
+        let output = self
+            .space
+            .outputs_for_element(&window)
+            .into_iter()
+            .next()
+            .or_else(|| self.space.outputs().next().cloned());
+        let Some(output_geo) = output.and_then(|output| self.space.output_geometry(&output)) else {
+            return;
+        };
+        let Some(window_geo) = self.space.element_geometry(&window) else {
+            return;
+        };
+
+        let size = bbox_from_surface_tree(popup.wl_surface(), (0, 0)).size;
+        let cursor = popup.text_input_rectangle();
+        let location = window_geo.loc + PopupKind::from(popup.clone()).location();
+
+        let mut offset = Point::<i32, Logical>::default();
+        let right = output_geo.loc.x + output_geo.size.w;
+        let bottom = output_geo.loc.y + output_geo.size.h;
+
+        if location.x + size.w > right {
+            offset.x = right - size.w - location.x;
+        }
+        if location.x + offset.x < output_geo.loc.x {
+            offset.x = output_geo.loc.x - location.x;
+        }
+        if location.y + size.h > bottom {
+            offset.y = -(size.h + cursor.size.h);
+        }
+        if location.y + offset.y < output_geo.loc.y {
+            offset.y = output_geo.loc.y - location.y;
+        }
+
+        if offset == Point::default() {
+            return;
+        }
+
+        // the popup follows the text input rectangle, so that's what gets moved
+        let mut popup = popup.clone();
+        popup.set_text_input_rectangle(
+            cursor.loc.x + offset.x,
+            cursor.loc.y + offset.y,
+            cursor.size.w,
+            cursor.size.h,
+        );
     }
 
     fn unconstrain_popup(&self, popup: &PopupSurface) {
@@ -141,7 +368,9 @@ impl<BackendData: Backend> WallyState<BackendData> {
         let output = self.space.outputs().next().unwrap();
 
         let output_geo = self.space.output_geometry(output).unwrap();
-        let window_geo = self.space.element_geometry(&window).unwrap();
+        let Some(window_geo) = self.space.element_geometry(&window) else {
+            return;
+        };
 
         // The target geometry for the positioner should be relative to its parent's geometry, so
         // we will compute that here.
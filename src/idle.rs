@@ -0,0 +1,40 @@
+use smithay::{utils::IsAlive, wayland::compositor::get_parent};
+
+use crate::{backend::Backend, state::WallyState};
+
+impl<BackendData: Backend> WallyState<BackendData> {
+    /// Reset the idle timers of all idle notifications
+    pub fn notify_activity(&mut self) {
+        self.idle_notifier_state.notify_activity(&self.seat);
+    }
+
+    /// Inhibit idle while a fullscreen window or a window holding an
+    /// idle inhibitor is visible on any output
+    pub fn refresh_idle_inhibit(&mut self) {
+        self.idle_inhibitors.retain(|surface| surface.alive());
+
+        let inhibitor_roots: Vec<_> = self
+            .idle_inhibitors
+            .iter()
+            .map(|surface| {
+                let mut root = surface.clone();
+                while let Some(parent) = get_parent(&root) {
+                    root = parent;
+                }
+                root
+            })
+            .collect();
+
+        // nothing is visible behind the lock screen
+        let inhibited = !self.is_locked()
+            && self.space.elements().any(|window| {
+                !self.space.outputs_for_element(window).is_empty()
+                    && (window.is_fullscreen()
+                        || inhibitor_roots
+                            .iter()
+                            .any(|root| window.surface_matches(root)))
+            });
+
+        self.idle_notifier_state.set_is_inhibited(inhibited);
+    }
+}
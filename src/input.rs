@@ -1,86 +1,108 @@
+use std::{any::Any, collections::HashMap};
+
 use smithay::{
     backend::input::{
-        AbsolutePositionEvent, Axis, AxisSource, ButtonState, Event, InputBackend, InputEvent,
-        KeyState, KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionEvent,
+        AbsolutePositionEvent, Axis, AxisSource, ButtonState, Device, Event, GestureBeginEvent,
+        GestureEndEvent, GesturePinchUpdateEvent as _, GestureSwipeUpdateEvent as _, InputBackend,
+        InputEvent, KeyState, KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent,
+        PointerMotionEvent,
     },
+    backend::renderer::utils::with_renderer_surface_state,
     input::{
         keyboard::FilterResult,
-        pointer::{AxisFrame, ButtonEvent, MotionEvent, RelativeMotionEvent},
+        pointer::{
+            AxisFrame, ButtonEvent, GestureHoldBeginEvent, GestureHoldEndEvent,
+            GesturePinchBeginEvent, GesturePinchEndEvent, GesturePinchUpdateEvent,
+            GestureSwipeBeginEvent, GestureSwipeEndEvent, GestureSwipeUpdateEvent, MotionEvent,
+            RelativeMotionEvent,
+        },
+    },
+    output::Output,
+    reexports::{input, wayland_server::protocol::wl_surface::WlSurface},
+    utils::{Logical, Point, Rectangle, Serial, SERIAL_COUNTER},
+    wayland::{
+        compositor::{RectangleKind, RegionAttributes},
+        input_method::InputMethodSeat,
+        pointer_constraints::{with_pointer_constraint, PointerConstraint},
+        seat::WaylandFocus,
     },
-    utils::{Logical, Point, SERIAL_COUNTER},
 };
 
-use crate::{backend::Backend, config::CONFIG, state::WallyState, types::keybind::Keybind};
+use crate::{backend::Backend, state::WallyState};
 
 impl<BackendData: Backend> WallyState<BackendData> {
-    pub fn process_input_event<I: InputBackend>(&mut self, event: InputEvent<I>) {
+    pub fn process_input_event<I: InputBackend>(&mut self, event: InputEvent<I>)
+    where
+        I::Device: 'static,
+    {
+        self.notify_activity();
+
         match event {
             InputEvent::Keyboard { event, .. } => {
                 let serial = SERIAL_COUNTER.next_serial();
                 let time = Event::time_msec(&event);
 
+                // while locked every key goes to the lock surface, whatever had focus before
+                self.focus_lock_surface();
+                self.restore_keymap();
+
                 let keyboard = self.seat.get_keyboard().unwrap();
+                let keycode = event.key_code();
+                let key_state = event.state();
+                let inhibitor = self.shortcuts_inhibitor();
+                let mut released = None;
+                let mut layout = None;
+
+                // keys the filter forwards reach an input method through its keyboard grab, one
+                // that lost the grab behind its back would never see them
+                if self.seat.input_method().keyboard_grabbed() && !keyboard.is_grabbed() {
+                    tracing::warn!("Input method grabbed the keyboard, but no grab is set");
+                }
 
-                if let Some(action) = keyboard.input(
+                let pressed = keyboard.input(
                     self,
-                    event.key_code(),
-                    event.state(),
+                    keycode,
+                    key_state,
                     serial,
                     time,
-                    |_state, modifiers_state, keysym_handle| {
-                        if let KeyState::Pressed = event.state() {
-                            // we should be able to get away with this since we wont have combo-binds
-                            let raw_syms = keysym_handle.raw_syms();
-                            let keysym = raw_syms.into_iter().next().unwrap();
-                            let keybind = Keybind::new(modifiers_state, keysym);
-
-                            if let Some(action) = CONFIG.keybinds.get(&keybind) {
-                                return FilterResult::Intercept(action.clone());
+                    |state, modifiers_state, keysym_handle| {
+                        layout = Some(modifiers_state.serialized.layout_effective);
+                        match key_state {
+                            KeyState::Pressed => {
+                                state.press_keybind(modifiers_state, &keysym_handle, inhibitor)
+                            }
+                            KeyState::Released => {
+                                released = state.release_keybind(keycode);
+                                FilterResult::Forward
                             }
                         }
-                        FilterResult::Forward
                     },
-                ) {
-                    tracing::info!(action = ?action, "Got action!");
-                    self.handle_action(action);
+                );
+
+                // xkb options like grp:toggle switch layouts without going through a binding
+                if let Some(layout) = layout {
+                    self.keyboard_layout_changed(layout);
+                }
+
+                if let Some(binding) = pressed.flatten().or(released) {
+                    self.trigger_binding(keycode, binding);
                 }
             }
             InputEvent::PointerMotion { event } => {
-                // TODO:
-                //     - Check whether pointer is "locked"
-                //         - Handle pointer lock
-                //     - Check whether pointer is "confined" and to what region
-                //         - Handle pointer confinement
+                // a hint left by a released pointer lock is where the pointer continues from
+                self.apply_cursor_position_hint();
 
-                let pointer_location = self.pointer.current_location();
-                let under = self.surface_under(pointer_location);
+                let pointer_location = self.pointer.current_location() + event.delta();
 
-                let serial = SERIAL_COUNTER.next_serial();
-
-                let pointer = self.pointer.clone();
-                pointer.relative_motion(
-                    self,
-                    under.clone(),
-                    &RelativeMotionEvent {
+                self.pointer_motion(
+                    pointer_location,
+                    event.time_msec(),
+                    Some(RelativeMotionEvent {
                         delta: event.delta(),
                         delta_unaccel: event.delta_unaccel(),
                         utime: event.time(),
-                    },
-                );
-
-                let pointer_location = self.clamp_coords(pointer_location);
-
-                pointer.motion(
-                    self,
-                    under,
-                    &MotionEvent {
-                        location: pointer_location,
-                        serial,
-                        time: event.time_msec(),
-                    },
+                    }),
                 );
-
-                pointer.frame(self);
             }
             InputEvent::PointerMotionAbsolute { event, .. } => {
                 let output = self.space.outputs().next().unwrap();
@@ -89,22 +111,7 @@ impl<BackendData: Backend> WallyState<BackendData> {
 
                 let pos = event.position_transformed(output_geo.size) + output_geo.loc.to_f64();
 
-                let serial = SERIAL_COUNTER.next_serial();
-
-                let pointer = self.pointer.clone();
-
-                let under = self.surface_under(pos);
-
-                pointer.motion(
-                    self,
-                    under,
-                    &MotionEvent {
-                        location: pos,
-                        serial,
-                        time: event.time_msec(),
-                    },
-                );
-                pointer.frame(self);
+                self.pointer_motion(pos, event.time_msec(), None);
             }
             InputEvent::PointerButton { event, .. } => {
                 let pointer = self.pointer.clone();
@@ -116,13 +123,25 @@ impl<BackendData: Backend> WallyState<BackendData> {
 
                 let button_state = event.state();
 
-                if ButtonState::Pressed == button_state && !pointer.is_grabbed() {
+                // clicking while holding a key cancels its release binding, like pressing a key does
+                if button_state == ButtonState::Pressed {
+                    self.cancel_release_binding();
+                }
+
+                if self.button_mousebind(button, button_state) {
+                    return;
+                }
+
+                if ButtonState::Pressed == button_state
+                    && !pointer.is_grabbed()
+                    && !self.is_locked()
+                {
                     if let Some((window, _loc)) = self
                         .space
                         .element_under(pointer.current_location())
                         .map(|(w, l)| (w.clone(), l))
                     {
-                        self.space.raise_element(&window, true);
+                        self.raise_window(&window, true);
 
                         self.space.elements().for_each(|window| {
                             window.send_pending_configure();
@@ -148,6 +167,10 @@ impl<BackendData: Backend> WallyState<BackendData> {
                 pointer.frame(self);
             }
             InputEvent::PointerAxis { event, .. } => {
+                if self.scroll_mousebind(&event) {
+                    return;
+                }
+
                 let source = event.source();
 
                 let horizontal_amount = event.amount(Axis::Horizontal).unwrap_or_else(|| {
@@ -186,10 +209,311 @@ impl<BackendData: Backend> WallyState<BackendData> {
                 pointer.axis(self, frame);
                 pointer.frame(self);
             }
+            InputEvent::GestureSwipeBegin { event, .. } => {
+                if self.intercepts_swipe(event.fingers()) {
+                    self.begin_swipe(event.fingers());
+                    return;
+                }
+
+                let pointer = self.pointer.clone();
+                pointer.gesture_swipe_begin(
+                    self,
+                    &GestureSwipeBeginEvent {
+                        serial: SERIAL_COUNTER.next_serial(),
+                        time: event.time_msec(),
+                        fingers: event.fingers(),
+                    },
+                );
+            }
+            InputEvent::GestureSwipeUpdate { event, .. } => {
+                if self.swipe.is_some() {
+                    self.update_swipe(event.delta(), event.time_msec());
+                    return;
+                }
+
+                let pointer = self.pointer.clone();
+                pointer.gesture_swipe_update(
+                    self,
+                    &GestureSwipeUpdateEvent {
+                        time: event.time_msec(),
+                        delta: event.delta(),
+                    },
+                );
+            }
+            InputEvent::GestureSwipeEnd { event, .. } => {
+                if self.swipe.is_some() {
+                    self.end_swipe(event.cancelled(), event.time_msec());
+                    return;
+                }
+
+                let pointer = self.pointer.clone();
+                pointer.gesture_swipe_end(
+                    self,
+                    &GestureSwipeEndEvent {
+                        serial: SERIAL_COUNTER.next_serial(),
+                        time: event.time_msec(),
+                        cancelled: event.cancelled(),
+                    },
+                );
+            }
+            InputEvent::GesturePinchBegin { event, .. } => {
+                let pointer = self.pointer.clone();
+                pointer.gesture_pinch_begin(
+                    self,
+                    &GesturePinchBeginEvent {
+                        serial: SERIAL_COUNTER.next_serial(),
+                        time: event.time_msec(),
+                        fingers: event.fingers(),
+                    },
+                );
+            }
+            InputEvent::GesturePinchUpdate { event, .. } => {
+                let pointer = self.pointer.clone();
+                pointer.gesture_pinch_update(
+                    self,
+                    &GesturePinchUpdateEvent {
+                        time: event.time_msec(),
+                        delta: event.delta(),
+                        scale: event.scale(),
+                        rotation: event.rotation(),
+                    },
+                );
+            }
+            InputEvent::GesturePinchEnd { event, .. } => {
+                let pointer = self.pointer.clone();
+                pointer.gesture_pinch_end(
+                    self,
+                    &GesturePinchEndEvent {
+                        serial: SERIAL_COUNTER.next_serial(),
+                        time: event.time_msec(),
+                        cancelled: event.cancelled(),
+                    },
+                );
+            }
+            InputEvent::GestureHoldBegin { event, .. } => {
+                let pointer = self.pointer.clone();
+                pointer.gesture_hold_begin(
+                    self,
+                    &GestureHoldBeginEvent {
+                        serial: SERIAL_COUNTER.next_serial(),
+                        time: event.time_msec(),
+                        fingers: event.fingers(),
+                    },
+                );
+            }
+            InputEvent::GestureHoldEnd { event, .. } => {
+                let pointer = self.pointer.clone();
+                pointer.gesture_hold_end(
+                    self,
+                    &GestureHoldEndEvent {
+                        serial: SERIAL_COUNTER.next_serial(),
+                        time: event.time_msec(),
+                        cancelled: event.cancelled(),
+                    },
+                );
+            }
+            InputEvent::TouchDown { event } => self.on_touch_down::<I>(event),
+            InputEvent::TouchMotion { event } => self.on_touch_motion::<I>(event),
+            InputEvent::TouchUp { event } => self.on_touch_up::<I>(event),
+            InputEvent::TouchCancel { event } => self.on_touch_cancel::<I>(event),
+            InputEvent::TouchFrame { .. } => self.on_touch_frame(),
+            InputEvent::TabletToolAxis { event } => self.on_tablet_tool_axis::<I>(event),
+            InputEvent::TabletToolProximity { event } => self.on_tablet_tool_proximity::<I>(event),
+            InputEvent::TabletToolTip { event } => self.on_tablet_tool_tip::<I>(event),
+            InputEvent::TabletToolButton { event } => self.on_tablet_tool_button::<I>(event),
+            InputEvent::DeviceAdded { device } => {
+                // only the devices of libinput based backends can be configured
+                if let Some(device) = (&device as &dyn Any).downcast_ref::<input::Device>() {
+                    self.add_libinput_device(device);
+                }
+                self.on_device_added(&device);
+            }
+            InputEvent::DeviceRemoved { device } => {
+                if let Some(device) = (&device as &dyn Any).downcast_ref::<input::Device>() {
+                    self.remove_libinput_device(device);
+                }
+                self.on_device_removed(&device);
+            }
             _ => {}
         }
     }
 
+    /// Move the pointer to a position already mapped to the outputs,
+    /// for absolute motion from sources other than an input backend
+    pub fn pointer_motion_absolute(&mut self, location: Point<f64, Logical>, time: u32) {
+        self.notify_activity();
+        self.pointer_motion(location, time, None);
+    }
+
+    /// Move the pointer to `location` unless an active pointer constraint prevents it
+    fn pointer_motion(
+        &mut self,
+        mut location: Point<f64, Logical>,
+        time: u32,
+        relative: Option<RelativeMotionEvent>,
+    ) {
+        let serial = SERIAL_COUNTER.next_serial();
+        let pointer = self.pointer.clone();
+
+        let current_location = pointer.current_location();
+        let under = self.surface_under(current_location);
+
+        let mut pointer_locked = false;
+        let mut pointer_confined = false;
+        let mut confine_region = None;
+
+        if let Some((surface, surface_location)) = under
+            .as_ref()
+            .and_then(|(target, loc)| Some((target.wl_surface()?, loc)))
+        {
+            with_pointer_constraint(&surface, &pointer, |constraint| match constraint {
+                Some(constraint) if constraint.is_active() => {
+                    // constraints only apply within their region
+                    let point = (current_location - *surface_location).to_i32_round();
+                    if !constraint
+                        .region()
+                        .map_or(true, |region| region.contains(point))
+                    {
+                        return;
+                    }
+
+                    match &*constraint {
+                        PointerConstraint::Locked(_) => pointer_locked = true,
+                        PointerConstraint::Confined(confine) => {
+                            pointer_confined = true;
+                            confine_region = confine.region().cloned();
+                        }
+                    }
+                }
+                _ => {}
+            });
+        }
+
+        // relative motion is reported even while the pointer is locked
+        if let Some(relative) = relative {
+            pointer.relative_motion(self, under.clone(), &relative);
+        }
+
+        if pointer_locked {
+            pointer.frame(self);
+            return;
+        }
+
+        location = self.clamp_coords(location);
+
+        // a confined pointer stops at the edge of its surface and the confinement region
+        if pointer_confined {
+            if let Some((surface, surface_location)) = under
+                .as_ref()
+                .and_then(|(target, loc)| Some((target.wl_surface()?.into_owned(), *loc)))
+            {
+                location = confine_location(
+                    location,
+                    current_location,
+                    &surface,
+                    surface_location,
+                    confine_region.as_ref(),
+                );
+            }
+        }
+
+        let new_under = self.surface_under(location);
+
+        // other surfaces may still be stacked above the confining one
+        if pointer_confined {
+            if let Some((target, _)) = &under {
+                let new_surface = new_under
+                    .as_ref()
+                    .and_then(|(new_target, _)| new_target.wl_surface());
+                if new_surface != target.wl_surface() {
+                    pointer.frame(self);
+                    return;
+                }
+            }
+        }
+
+        pointer.motion(
+            self,
+            new_under.clone(),
+            &MotionEvent {
+                location,
+                serial,
+                time,
+            },
+        );
+        pointer.frame(self);
+
+        // activate constraints of the surface the pointer moved into
+        if let Some((surface, surface_location)) =
+            new_under.and_then(|(target, loc)| Some((target.wl_surface()?.into_owned(), loc)))
+        {
+            with_pointer_constraint(&surface, &pointer, |constraint| match constraint {
+                Some(constraint) if !constraint.is_active() => {
+                    let point = (location - surface_location).to_i32_round();
+                    if constraint
+                        .region()
+                        .map_or(true, |region| region.contains(point))
+                    {
+                        constraint.activate();
+                    }
+                }
+                _ => {}
+            });
+        }
+    }
+
+    /// The output an absolute input device is mapped to by device name in `outputs`,
+    /// falling back to the first monitor
+    pub fn mapped_output<D: Device>(
+        &self,
+        device: &D,
+        outputs: &HashMap<String, String>,
+    ) -> Option<Output> {
+        let configured = outputs.get(&device.name()).and_then(|name| {
+            self.monitors
+                .iter()
+                .find(|monitor| &monitor.output_ref().name() == name)
+        });
+
+        configured
+            .or_else(|| self.monitors.first())
+            .map(|monitor| monitor.output_clone())
+    }
+
+    /// Position of an absolute input event within the output its device is mapped to
+    pub fn mapped_position<I: InputBackend>(
+        &self,
+        event: &impl AbsolutePositionEvent<I>,
+        outputs: &HashMap<String, String>,
+    ) -> Option<Point<f64, Logical>> {
+        let output = self.mapped_output(&event.device(), outputs)?;
+        let output_geometry = self.space.output_geometry(&output)?;
+
+        Some(event.position_transformed(output_geometry.size) + output_geometry.loc.to_f64())
+    }
+
+    /// Focus and raise the window at `location`, like clicking it would
+    pub fn focus_window_under(&mut self, location: Point<f64, Logical>, serial: Serial) {
+        if self.is_locked() {
+            return;
+        }
+
+        let Some(window) = self
+            .space
+            .element_under(location)
+            .map(|(window, _)| window.clone())
+        else {
+            return;
+        };
+
+        self.raise_window(&window, true);
+
+        if let Some(surface) = window.wl_surface() {
+            let keyboard = self.seat.get_keyboard().unwrap();
+            keyboard.set_focus(self, Some(surface.into_owned()), serial);
+        }
+    }
+
     /// Adjust a coordinate point to within the total space of all outputs
     fn clamp_coords(&self, pos: Point<f64, Logical>) -> Point<f64, Logical> {
         if self.space.outputs().next().is_none() {
@@ -221,3 +545,61 @@ impl<BackendData: Backend> WallyState<BackendData> {
         (clamped_x, clamped_y).into()
     }
 }
+
+/// Keep a confined pointer within the bounds of its surface and the confinement region.
+/// For regions that aren't a single rectangle, motion along just one axis is tried before
+/// staying at the previous location
+fn confine_location(
+    location: Point<f64, Logical>,
+    previous: Point<f64, Logical>,
+    surface: &WlSurface,
+    surface_location: Point<f64, Logical>,
+    region: Option<&RegionAttributes>,
+) -> Point<f64, Logical> {
+    let Some(size) = with_renderer_surface_state(surface, |state| state.surface_size()).flatten()
+    else {
+        return previous;
+    };
+
+    let mut bounds = Rectangle::new((0, 0).into(), size);
+    if let Some(region) = region {
+        let added = region
+            .rects
+            .iter()
+            .filter(|(kind, _)| matches!(kind, RectangleKind::Add))
+            .map(|(_, rect)| *rect)
+            .reduce(|merged, rect| merged.merge(rect));
+        match added.and_then(|added| added.intersection(bounds)) {
+            Some(intersection) => bounds = intersection,
+            None => return previous,
+        }
+    }
+
+    // the last pixel of the bounds is still inside
+    let local = location - surface_location;
+    let clamped: Point<f64, Logical> = (
+        local.x.clamp(
+            bounds.loc.x as f64,
+            (bounds.loc.x + bounds.size.w) as f64 - 1.0,
+        ),
+        local.y.clamp(
+            bounds.loc.y as f64,
+            (bounds.loc.y + bounds.size.h) as f64 - 1.0,
+        ),
+    )
+        .into();
+    let previous_local = previous - surface_location;
+
+    let inside = |point: Point<f64, Logical>| {
+        region.is_none_or(|region| region.contains(point.to_i32_floor()))
+    };
+
+    [
+        clamped,
+        (clamped.x, previous_local.y).into(),
+        (previous_local.x, clamped.y).into(),
+    ]
+    .into_iter()
+    .find(|point| inside(*point))
+    .map_or(previous, |point| point + surface_location)
+}
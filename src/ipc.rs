@@ -0,0 +1,320 @@
+use std::{
+    collections::HashMap,
+    io::{ErrorKind, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+};
+
+use serde::Serialize;
+use smithay::{
+    input::keyboard::LedState,
+    reexports::calloop::{
+        generic::Generic, Interest, LoopHandle, Mode, PostAction, RegistrationToken,
+    },
+};
+use tracing::{info, warn};
+
+use crate::{backend::Backend, state::WallyState};
+
+/// Environment variable pointing clients spawned by the compositor to the IPC socket
+pub const SOCKET_ENV: &str = "WALLY_SOCK";
+
+/// Longest request accepted, longer lines are answered with an error
+const MAX_REQUEST_LEN: usize = 1024;
+/// Output a subscriber may fall behind by before it is disconnected
+const MAX_PENDING_LEN: usize = 64 * 1024;
+
+/// Messages sent to IPC clients, both in reply to requests and to subscribers.
+/// Each message is written as a single line of JSON.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IpcMessage {
+    KeyboardLayout {
+        index: u32,
+        name: String,
+    },
+    LockState {
+        caps: bool,
+        num: bool,
+        scroll: bool,
+    },
+    /// `None` for the regular keybinds
+    BindingMode {
+        name: Option<String>,
+    },
+    /// Only sent to subscribers, whenever a window changes its app_id or title
+    WindowChanged {
+        app_id: Option<String>,
+        title: Option<String>,
+    },
+    Error(String),
+}
+
+impl From<LedState> for IpcMessage {
+    fn from(led_state: LedState) -> Self {
+        Self::LockState {
+            caps: led_state.caps.unwrap_or_default(),
+            num: led_state.num.unwrap_or_default(),
+            scroll: led_state.scroll.unwrap_or_default(),
+        }
+    }
+}
+
+/// A unix socket accepting one request per connection, written as a single line:
+///
+/// - `keyboard_layout` replies with the active keyboard layout
+/// - `lock_state` replies with the state of caps, num and scroll lock
+/// - `binding_mode` replies with the active binding mode
+/// - `subscribe` keeps the connection open and sends every change as it happens
+#[derive(Debug)]
+pub struct IpcState {
+    socket_path: PathBuf,
+    clients: HashMap<u64, IpcClient>,
+    next_client: u64,
+}
+
+/// A connected client, its socket is never blocked on
+#[derive(Debug)]
+struct IpcClient {
+    stream: UnixStream,
+    token: RegistrationToken,
+    /// Request line received so far
+    request: Vec<u8>,
+    /// Output the socket has not accepted yet
+    pending: Vec<u8>,
+    subscribed: bool,
+    /// The reply has been queued, the connection is closed once it is sent
+    replied: bool,
+}
+
+impl IpcClient {
+    /// Read everything available, returns false once the connection is gone
+    fn receive(&mut self) -> bool {
+        let mut buffer = [0; 1024];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return false,
+                // subscribers have nothing more to say
+                Ok(_) if self.subscribed || self.replied => {}
+                Ok(len) => self.request.extend_from_slice(&buffer[..len]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return true,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+    }
+
+    /// Take the request line, once it is complete
+    fn take_request(&mut self) -> Option<String> {
+        if self.subscribed || self.replied {
+            return None;
+        }
+
+        let line = match self.request.iter().position(|byte| *byte == b'\n') {
+            Some(end) => &self.request[..end],
+            None if self.request.len() > MAX_REQUEST_LEN => &self.request,
+            None => return None,
+        };
+        let request = String::from_utf8_lossy(line).trim().to_string();
+        self.request = Vec::new();
+
+        Some(request)
+    }
+
+    /// Queue a message, returns false if the client is too far behind to take it
+    fn queue(&mut self, message: &IpcMessage) -> bool {
+        let Ok(mut line) = serde_json::to_string(message) else {
+            return false;
+        };
+        line.push('\n');
+
+        if self.pending.len() + line.len() > MAX_PENDING_LEN {
+            return false;
+        }
+
+        self.pending.extend_from_slice(line.as_bytes());
+        self.flush()
+    }
+
+    /// Write as much pending output as the socket takes, returns false once the connection is gone
+    fn flush(&mut self) -> bool {
+        while !self.pending.is_empty() {
+            match self.stream.write(&self.pending) {
+                Ok(0) => return false,
+                Ok(len) => {
+                    self.pending.drain(..len);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return true,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+
+        true
+    }
+
+    fn finished(&self) -> bool {
+        self.replied && self.pending.is_empty()
+    }
+}
+
+impl IpcState {
+    pub fn new<BackendData: Backend + 'static>(
+        loop_handle: &LoopHandle<'static, WallyState<BackendData>>,
+        socket_name: &str,
+    ) -> Option<Self> {
+        let runtime_dir = std::env::var("XDG_RUNTIME_DIR").ok()?;
+        let socket_path = PathBuf::from(runtime_dir).join(format!("wally-{socket_name}.sock"));
+
+        // a previous instance might not have cleaned up after itself
+        let _ = std::fs::remove_file(&socket_path);
+
+        let listener = match UnixListener::bind(&socket_path) {
+            Ok(listener) => listener,
+            Err(err) => {
+                warn!("Failed to bind the IPC socket: {err}");
+                return None;
+            }
+        };
+        listener.set_nonblocking(true).ok()?;
+
+        loop_handle
+            .insert_source(
+                Generic::new(listener, Interest::READ, Mode::Level),
+                |_, listener, state| {
+                    while let Ok((stream, _)) = listener.accept() {
+                        state.add_ipc_client(stream);
+                    }
+                    Ok(PostAction::Continue)
+                },
+            )
+            .ok()?;
+
+        info!(path = ?socket_path, "IPC socket ready");
+
+        Some(Self {
+            socket_path,
+            clients: HashMap::new(),
+            next_client: 0,
+        })
+    }
+
+    pub fn socket_path(&self) -> &PathBuf {
+        &self.socket_path
+    }
+}
+
+impl Drop for IpcState {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.socket_path);
+    }
+}
+
+impl<BackendData: Backend> WallyState<BackendData> {
+    fn add_ipc_client(&mut self, stream: UnixStream) {
+        let Some(ipc) = self.ipc.as_mut() else {
+            return;
+        };
+        let Ok(source) = stream
+            .set_nonblocking(true)
+            .and_then(|_| stream.try_clone())
+        else {
+            return;
+        };
+
+        let id = ipc.next_client;
+        ipc.next_client += 1;
+
+        // edge triggered, so a client waiting for its output to be read doesn't keep waking us up
+        let token = self.loop_handle.insert_source(
+            Generic::new(source, Interest::BOTH, Mode::Edge),
+            move |_, _, state| Ok(state.ipc_client_ready(id)),
+        );
+        let Ok(token) = token else {
+            return;
+        };
+
+        ipc.clients.insert(
+            id,
+            IpcClient {
+                stream,
+                token,
+                request: Vec::new(),
+                pending: Vec::new(),
+                subscribed: false,
+                replied: false,
+            },
+        );
+    }
+
+    fn ipc_client_ready(&mut self, id: u64) -> PostAction {
+        let Some(client) = self.ipc.as_mut().and_then(|ipc| ipc.clients.get_mut(&id)) else {
+            return PostAction::Remove;
+        };
+
+        if !client.receive() {
+            return self.remove_ipc_client(id);
+        }
+
+        if let Some(request) = client.take_request() {
+            let reply = match request.as_str() {
+                "subscribe" => None,
+                "keyboard_layout" => {
+                    let (index, name) = self.keyboard_layout();
+                    Some(IpcMessage::KeyboardLayout { index, name })
+                }
+                "lock_state" => Some(IpcMessage::from(self.led_state)),
+                "binding_mode" => Some(IpcMessage::BindingMode {
+                    name: self.binding_mode().map(String::from),
+                }),
+                request => Some(IpcMessage::Error(format!("unknown request: {request}"))),
+            };
+
+            let Some(client) = self.ipc.as_mut().and_then(|ipc| ipc.clients.get_mut(&id)) else {
+                return PostAction::Remove;
+            };
+            match reply {
+                Some(reply) => {
+                    client.replied = true;
+                    client.queue(&reply);
+                }
+                None => client.subscribed = true,
+            }
+        }
+
+        let Some(client) = self.ipc.as_mut().and_then(|ipc| ipc.clients.get_mut(&id)) else {
+            return PostAction::Remove;
+        };
+        if !client.flush() || client.finished() {
+            return self.remove_ipc_client(id);
+        }
+
+        PostAction::Continue
+    }
+
+    /// Forget a client, the connection is closed along with its event source
+    fn remove_ipc_client(&mut self, id: u64) -> PostAction {
+        if let Some(ipc) = self.ipc.as_mut() {
+            ipc.clients.remove(&id);
+        }
+
+        PostAction::Remove
+    }
+
+    /// Send a message to every subscribed IPC client, clients too far behind are dropped
+    pub fn ipc_broadcast(&mut self, message: IpcMessage) {
+        let Some(ipc) = self.ipc.as_mut() else {
+            return;
+        };
+
+        let loop_handle = &self.loop_handle;
+        ipc.clients.retain(|_, client| {
+            if !client.subscribed || client.queue(&message) {
+                return true;
+            }
+
+            loop_handle.remove(client.token);
+            false
+        });
+    }
+}
@@ -0,0 +1,293 @@
+use std::{collections::HashSet, time::Duration};
+
+use smithay::{
+    backend::input::{Axis, ButtonState, InputBackend, PointerAxisEvent},
+    input::keyboard::{FilterResult, Keycode, Keysym, KeysymHandle, ModifiersState},
+    reexports::calloop::{
+        timer::{TimeoutAction, Timer},
+        RegistrationToken,
+    },
+    utils::SERIAL_COUNTER,
+    wayland::keyboard_shortcuts_inhibit::{
+        KeyboardShortcutsInhibitor, KeyboardShortcutsInhibitorSeat,
+    },
+};
+
+use crate::{
+    backend::Backend,
+    config::CONFIG,
+    ipc::IpcMessage,
+    state::WallyState,
+    types::keybind::{Action, Binding, Keybind, MouseBind, MouseTrigger},
+};
+
+#[derive(Debug, Default)]
+pub struct KeybindState {
+    /// Release binding of the last pressed key, dropped as soon as another key is pressed
+    pending_release: Option<(Keycode, Binding)>,
+    /// Held key repeating the action of its binding
+    repeat: Option<(Keycode, RegistrationToken)>,
+    /// Name of the active binding mode, `None` for the regular keybinds
+    mode: Option<String>,
+    /// Bound buttons that were pressed, their release is not forwarded either
+    pressed_buttons: HashSet<u32>,
+    /// Bound scrolling that has not added up to a whole step yet, in 1/120ths of a step
+    scroll: (f64, f64),
+}
+
+/// Scroll of an axis in 1/120ths of a wheel step, treating 15 pixels of continuous scrolling as a step
+fn scroll_v120<I: InputBackend>(event: &impl PointerAxisEvent<I>, axis: Axis) -> f64 {
+    event
+        .amount_v120(axis)
+        .or_else(|| event.amount(axis).map(|amount| amount * 120.0 / 15.0))
+        .unwrap_or(0.0)
+}
+
+impl<BackendData: Backend> WallyState<BackendData> {
+    /// Inhibitor of the surface with keyboard focus, looked up before the keyboard filter
+    /// runs since the keyboard can't be queried from within it
+    pub fn shortcuts_inhibitor(&self) -> Option<KeyboardShortcutsInhibitor> {
+        let keyboard = self.seat.get_keyboard().unwrap();
+        let focus = keyboard.current_focus()?;
+        self.seat.keyboard_shortcuts_inhibitor_for_surface(&focus)
+    }
+
+    /// Keyboard filter for key presses, intercepting keys bound to an action.
+    /// Intercepted keys without a binding are swallowed by the active binding mode
+    pub fn press_keybind(
+        &mut self,
+        modifiers_state: &ModifiersState,
+        keysym_handle: &KeysymHandle<'_>,
+        inhibitor: Option<KeyboardShortcutsInhibitor>,
+    ) -> FilterResult<Option<Binding>> {
+        // pressing any key stops a repeating binding and cancels a tap
+        self.stop_binding_repeat();
+        self.keybind_state.pending_release = None;
+
+        let keysyms = keysym_handle.raw_syms();
+        let locked = self.is_locked();
+        let inhibited = inhibitor
+            .as_ref()
+            .is_some_and(|inhibitor| inhibitor.is_active());
+
+        if let Some(inhibitor) = inhibitor {
+            let escape = keysyms.iter().any(|keysym| {
+                Keybind::new(modifiers_state, *keysym) == CONFIG.shortcuts_inhibit_escape
+            });
+
+            if escape {
+                if inhibitor.is_active() {
+                    inhibitor.inactivate();
+                } else {
+                    inhibitor.activate();
+                }
+                return FilterResult::Intercept(None);
+            }
+        }
+
+        // the lock surface and inhibiting clients get the keys a mode would swallow
+        let mode = self
+            .keybind_state
+            .mode
+            .as_ref()
+            .filter(|_| !locked && !inhibited)
+            .and_then(|name| CONFIG.binding_modes.get(name));
+        let keybinds = mode.map_or(&CONFIG.keybinds, |mode| &mode.keybinds);
+
+        let binding = keysyms
+            .iter()
+            .find_map(|keysym| keybinds.get(&Keybind::new(modifiers_state, *keysym)))
+            .filter(|binding| {
+                (!locked || binding.allow_when_locked)
+                    && (!inhibited || binding.allow_when_inhibited)
+            });
+
+        let Some(binding) = binding else {
+            return match mode {
+                Some(_) if keysyms.contains(&Keysym::Escape) => {
+                    FilterResult::Intercept(Some(Action::ExitMode.into()))
+                }
+                Some(mode) if !mode.passthrough => FilterResult::Intercept(None),
+                _ => FilterResult::Forward,
+            };
+        };
+
+        if binding.on_release {
+            self.keybind_state.pending_release = Some((keysym_handle.raw_code(), binding.clone()));
+            return FilterResult::Forward;
+        }
+
+        FilterResult::Intercept(Some(binding.clone()))
+    }
+
+    /// Keyboard filter for key releases, returning the release binding tapped with the key.
+    /// Releases are always forwarded, clients have seen the press of release bindings
+    pub fn release_keybind(&mut self, keycode: Keycode) -> Option<Binding> {
+        if self
+            .keybind_state
+            .repeat
+            .as_ref()
+            .is_some_and(|(code, _)| *code == keycode)
+        {
+            self.stop_binding_repeat();
+        }
+
+        self.keybind_state
+            .pending_release
+            .take_if(|(code, _)| *code == keycode)
+            .map(|(_, binding)| binding)
+    }
+
+    /// Cancel the release binding of a held key, it no longer is a tap
+    pub fn cancel_release_binding(&mut self) {
+        self.keybind_state.pending_release = None;
+    }
+
+    pub fn trigger_binding(&mut self, keycode: Keycode, binding: Binding) {
+        tracing::info!(action = ?binding.action, "Got action!");
+
+        if binding.repeat && !binding.on_release && CONFIG.repeat_rate > 0 {
+            self.start_binding_repeat(keycode, &binding);
+        }
+
+        self.handle_action(binding.action);
+    }
+
+    /// A mousebind firing stops a repeating binding and cancels a tap, like a key press would
+    fn interrupt_keybinds(&mut self) {
+        self.stop_binding_repeat();
+        self.cancel_release_binding();
+    }
+
+    fn mousebind(&self, trigger: MouseTrigger) -> Option<&'static Action> {
+        if self.is_locked() {
+            return None;
+        }
+
+        let modifiers = self.seat.get_keyboard().unwrap().modifier_state();
+        CONFIG.mousebinds.get(&MouseBind::new(&modifiers, trigger))
+    }
+
+    /// Trigger the binding of a mouse button, returning whether the button is intercepted
+    pub fn button_mousebind(&mut self, button: u32, button_state: ButtonState) -> bool {
+        if button_state == ButtonState::Released {
+            return self.keybind_state.pressed_buttons.remove(&button);
+        }
+
+        let Some(action) = self.mousebind(MouseTrigger::Button(button)) else {
+            return false;
+        };
+
+        // bindings act on the window under the pointer, like clicking it would
+        let location = self.pointer.current_location();
+        self.focus_window_under(location, SERIAL_COUNTER.next_serial());
+
+        self.interrupt_keybinds();
+        self.keybind_state.pressed_buttons.insert(button);
+        self.handle_action(action.clone());
+        true
+    }
+
+    /// Trigger the bindings of scrolling once per step, returning whether the scrolling is
+    /// intercepted. Scrolling along an axis is intercepted if any of its directions is bound
+    pub fn scroll_mousebind<I: InputBackend>(&mut self, event: &impl PointerAxisEvent<I>) -> bool {
+        let horizontal = self
+            .mousebind(MouseTrigger::ScrollLeft)
+            .or(self.mousebind(MouseTrigger::ScrollRight))
+            .is_some();
+        let vertical = self
+            .mousebind(MouseTrigger::ScrollUp)
+            .or(self.mousebind(MouseTrigger::ScrollDown))
+            .is_some();
+
+        if !horizontal && !vertical {
+            self.keybind_state.scroll = (0.0, 0.0);
+            return false;
+        }
+
+        let mut triggers = Vec::new();
+        let (mut scroll_x, mut scroll_y) = self.keybind_state.scroll;
+
+        if horizontal {
+            scroll_x += scroll_v120(event, Axis::Horizontal);
+            while scroll_x.abs() >= 120.0 {
+                triggers.push(if scroll_x > 0.0 {
+                    MouseTrigger::ScrollRight
+                } else {
+                    MouseTrigger::ScrollLeft
+                });
+                scroll_x -= 120.0f64.copysign(scroll_x);
+            }
+        }
+
+        if vertical {
+            scroll_y += scroll_v120(event, Axis::Vertical);
+            while scroll_y.abs() >= 120.0 {
+                triggers.push(if scroll_y > 0.0 {
+                    MouseTrigger::ScrollDown
+                } else {
+                    MouseTrigger::ScrollUp
+                });
+                scroll_y -= 120.0f64.copysign(scroll_y);
+            }
+        }
+
+        self.keybind_state.scroll = (scroll_x, scroll_y);
+
+        if !triggers.is_empty() {
+            self.interrupt_keybinds();
+        }
+
+        for trigger in triggers {
+            if let Some(action) = self.mousebind(trigger) {
+                self.handle_action(action.clone());
+            }
+        }
+
+        true
+    }
+
+    pub fn binding_mode(&self) -> Option<&str> {
+        self.keybind_state.mode.as_deref()
+    }
+
+    pub fn set_binding_mode(&mut self, mode: Option<String>) {
+        if let Some(name) = mode
+            .as_ref()
+            .filter(|name| !CONFIG.binding_modes.contains_key(*name))
+        {
+            tracing::warn!("Unknown binding mode {name}");
+            return;
+        }
+
+        self.stop_binding_repeat();
+        self.keybind_state.pending_release = None;
+        self.keybind_state.mode = mode.clone();
+        self.ipc_broadcast(IpcMessage::BindingMode { name: mode });
+    }
+
+    fn start_binding_repeat(&mut self, keycode: Keycode, binding: &Binding) {
+        let delay = Duration::from_millis(CONFIG.repeat_delay as u64);
+        let interval = Duration::from_secs_f64(1.0 / CONFIG.repeat_rate as f64);
+        let action = binding.action.clone();
+
+        let token = self.loop_handle.insert_source(
+            Timer::from_duration(delay),
+            move |_, _, state: &mut WallyState<BackendData>| {
+                state.handle_action(action.clone());
+                TimeoutAction::ToDuration(interval)
+            },
+        );
+
+        match token {
+            Ok(token) => self.keybind_state.repeat = Some((keycode, token)),
+            Err(err) => tracing::warn!("Failed to start repeating the binding: {err}"),
+        }
+    }
+
+    fn stop_binding_repeat(&mut self) {
+        if let Some((_, token)) = self.keybind_state.repeat.take() {
+            self.loop_handle.remove(token);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use smithay::{
+    input::keyboard::LedState,
+    reexports::input::{self, DeviceCapability, DeviceConfigError, Led},
+};
+use tracing::{debug, warn};
+
+use crate::{
+    backend::Backend, config::CONFIG, state::WallyState, types::input_device::InputDeviceConfig,
+};
+
+impl<BackendData: Backend> WallyState<BackendData> {
+    /// Configure a device added by a libinput based backend, keyboards get their LEDs
+    /// set and are kept to follow the lock state
+    pub fn add_libinput_device(&mut self, device: &input::Device) {
+        let mut device = device.clone();
+        configure_device(&mut device);
+
+        if device.has_capability(DeviceCapability::Keyboard) {
+            update_leds([&mut device], self.led_state);
+            self.libinput_keyboards.push(device);
+        }
+    }
+
+    pub fn remove_libinput_device(&mut self, device: &input::Device) {
+        self.libinput_keyboards
+            .retain(|keyboard| keyboard != device);
+    }
+}
+
+/// Apply every matching `input_devices` section from the config to a newly added device,
+/// later sections take precedence over earlier ones
+fn configure_device(device: &mut input::Device) {
+    for config in CONFIG.input_devices.iter().filter(|c| c.matches(device)) {
+        debug!(device = device.name(), ?config, "Configuring input device");
+        apply(device, config);
+    }
+}
+
+fn apply(device: &mut input::Device, config: &InputDeviceConfig) {
+    fn check(device: &input::Device, option: &str, result: Result<(), DeviceConfigError>) {
+        // unsupported options are expected when matching on kind, e.g. dwt on a mouse
+        if let Err(DeviceConfigError::Invalid) = result {
+            warn!(device = device.name(), "Invalid value for {option}");
+        }
+    }
+
+    if let Some(enabled) = config.tap {
+        let result = device.config_tap_set_enabled(enabled);
+        check(device, "tap", result);
+    }
+    if let Some(enabled) = config.natural_scroll {
+        let result = device.config_scroll_set_natural_scroll_enabled(enabled);
+        check(device, "natural_scroll", result);
+    }
+    if let Some(profile) = config.accel_profile {
+        let result = device.config_accel_set_profile(profile);
+        check(device, "accel_profile", result);
+    }
+    if let Some(speed) = config.accel_speed {
+        let result = device.config_accel_set_speed(speed);
+        check(device, "accel_speed", result);
+    }
+    if let Some(enabled) = config.dwt {
+        let result = device.config_dwt_set_enabled(enabled);
+        check(device, "dwt", result);
+    }
+    if let Some(method) = config.scroll_method {
+        let result = device.config_scroll_set_method(method);
+        check(device, "scroll_method", result);
+    }
+    if let Some(enabled) = config.left_handed {
+        let result = device.config_left_handed_set(enabled);
+        check(device, "left_handed", result);
+    }
+    if let Some(enabled) = config.middle_emulation {
+        let result = device.config_middle_emulation_set_enabled(enabled);
+        check(device, "middle_emulation", result);
+    }
+}
+
+/// Light up the lock LEDs of physical keyboards
+pub fn update_leds<'a>(
+    devices: impl IntoIterator<Item = &'a mut input::Device>,
+    led_state: LedState,
+) {
+    let mut leds = Led::empty();
+    leds.set(Led::CAPSLOCK, led_state.caps.unwrap_or_default());
+    leds.set(Led::NUMLOCK, led_state.num.unwrap_or_default());
+    leds.set(Led::SCROLLLOCK, led_state.scroll.unwrap_or_default());
+
+    for device in devices {
+        device.led_update(leds);
+    }
+}
@@ -0,0 +1,111 @@
+use smithay::{
+    output::Output,
+    utils::{IsAlive, Logical, Point, SERIAL_COUNTER},
+    wayland::session_lock::{LockSurface, SessionLocker},
+};
+
+use crate::{backend::Backend, focus::PointerFocusTarget, state::WallyState};
+
+#[derive(Debug, Default)]
+pub enum LockState {
+    #[default]
+    Unlocked,
+    /// Outputs are blanked, but the locker has not been told yet that the session is locked
+    Locking(SessionLocker),
+    /// Stays in effect until the locker unlocks the session, even if it crashes
+    Locked,
+}
+
+impl LockState {
+    pub fn is_locked(&self) -> bool {
+        !matches!(self, LockState::Unlocked)
+    }
+}
+
+impl<BackendData: Backend> WallyState<BackendData> {
+    pub fn is_locked(&self) -> bool {
+        self.lock_state.is_locked()
+    }
+
+    /// Confirm a pending lock, must only be called once every output rendered a locked frame
+    pub fn confirm_lock(&mut self) {
+        if !matches!(self.lock_state, LockState::Locking(_)) {
+            return;
+        }
+
+        if let LockState::Locking(locker) =
+            std::mem::replace(&mut self.lock_state, LockState::Locked)
+        {
+            locker.lock();
+        }
+    }
+
+    pub fn lock_surface(&self, output: &Output) -> Option<LockSurface> {
+        self.monitors
+            .iter()
+            .find(|monitor| monitor.output_ref() == output)
+            .and_then(|monitor| monitor.lock_surface())
+            .filter(|surface| surface.alive())
+            .cloned()
+    }
+
+    /// Size every lock surface to cover its output
+    pub fn configure_lock_surfaces(&self) {
+        for monitor in &self.monitors {
+            let Some(surface) = monitor.lock_surface() else {
+                continue;
+            };
+            let Some(geometry) = self.space.output_geometry(monitor.output_ref()) else {
+                continue;
+            };
+
+            surface.with_pending_state(|state| {
+                state.size = Some((geometry.size.w as u32, geometry.size.h as u32).into());
+            });
+            surface.send_configure();
+        }
+    }
+
+    /// Move keyboard focus to the lock surface of the active monitor, or any other lock surface
+    pub fn focus_lock_surface(&mut self) {
+        if !self.is_locked() {
+            return;
+        }
+
+        let active = self
+            .active_monitor_index()
+            .and_then(|index| self.lock_surface(&self.monitors[index].output_clone()));
+        let surface = active.or_else(|| {
+            self.monitors
+                .iter()
+                .find_map(|monitor| monitor.lock_surface().filter(|surface| surface.alive()))
+                .cloned()
+        });
+
+        let keyboard = self.seat.get_keyboard().unwrap();
+        let surface = surface.map(|surface| surface.wl_surface().clone());
+
+        if keyboard.current_focus() != surface {
+            keyboard.set_focus(self, surface, SERIAL_COUNTER.next_serial());
+        }
+    }
+
+    /// The lock surface under a point of the space, used in place of windows while locked
+    pub fn lock_surface_under(
+        &self,
+        pos: Point<f64, Logical>,
+    ) -> Option<(PointerFocusTarget, Point<f64, Logical>)> {
+        self.monitors.iter().find_map(|monitor| {
+            let geometry = self.space.output_geometry(monitor.output_ref())?;
+            if !geometry.to_f64().contains(pos) {
+                return None;
+            }
+
+            let surface = monitor.lock_surface().filter(|surface| surface.alive())?;
+            Some((
+                PointerFocusTarget::from(surface.wl_surface()),
+                geometry.loc.to_f64(),
+            ))
+        })
+    }
+}
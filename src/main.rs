@@ -1,13 +1,28 @@
 mod backend;
+mod capture;
+mod clipboard;
 mod config;
+mod cursor;
 mod elements;
 mod focus;
+mod gesture;
 mod handlers;
+mod idle;
 mod input;
+mod ipc;
+mod keybind;
+mod libinput;
+mod lock;
 mod monitor;
+mod output;
+mod protocols;
+mod scratchpad;
 mod state;
+mod tablet;
+mod touch;
 mod types;
 mod util;
+mod virtual_input;
 mod workspace;
 
 use clap::{Parser, ValueEnum};
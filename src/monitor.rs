@@ -1,19 +1,32 @@
-use smithay::output::Output;
+use smithay::{
+    desktop::Space,
+    output::Output,
+    utils::{Logical, Point},
+    wayland::session_lock::LockSurface,
+};
 
-use crate::{config::CONFIG, workspace::Workspace};
+use crate::{config::CONFIG, elements::window::WindowElement, workspace::Workspace};
 
 #[derive(Debug)]
 pub struct Monitor {
     workspaces: Vec<Workspace>,
+    active_workspace: usize,
     output: Output,
+    /// Rendered in place of the workspaces while the session is locked
+    lock_surface: Option<LockSurface>,
 }
 
 impl Monitor {
     pub fn new(output: Output) -> Self {
-        let workspaces = (0..=CONFIG.workspace_count)
+        let workspaces = (0..CONFIG.workspace_count)
             .map(|_| Workspace::default())
             .collect();
-        Self { workspaces, output }
+        Self {
+            workspaces,
+            active_workspace: 0,
+            output,
+            lock_surface: None,
+        }
     }
 
     pub fn output_ref(&self) -> &Output {
@@ -23,4 +36,136 @@ impl Monitor {
     pub fn output_clone(&self) -> Output {
         self.output.clone()
     }
+
+    pub fn lock_surface(&self) -> Option<&LockSurface> {
+        self.lock_surface.as_ref()
+    }
+
+    pub fn set_lock_surface(&mut self, surface: Option<LockSurface>) {
+        self.lock_surface = surface;
+    }
+
+    pub fn active_workspace_index(&self) -> usize {
+        self.active_workspace
+    }
+
+    pub fn workspace_count(&self) -> usize {
+        self.workspaces.len()
+    }
+
+    pub fn workspace_mut(&mut self, index: usize) -> Option<&mut Workspace> {
+        self.workspaces.get_mut(index)
+    }
+
+    pub fn active_workspace_mut(&mut self) -> &mut Workspace {
+        &mut self.workspaces[self.active_workspace]
+    }
+
+    /// Index of the workspace holding the given window
+    pub fn workspace_of(&self, window: &WindowElement) -> Option<usize> {
+        self.workspaces
+            .iter()
+            .position(|workspace| workspace.contains(window))
+    }
+
+    /// Remove a window from whichever workspace holds it, returning its last known location
+    pub fn remove_window(&mut self, window: &WindowElement) -> Option<Point<i32, Logical>> {
+        self.workspaces
+            .iter_mut()
+            .find_map(|workspace| workspace.remove_window(window))
+    }
+
+    pub fn windows(&self) -> impl Iterator<Item = &WindowElement> {
+        self.workspaces
+            .iter()
+            .flat_map(|workspace| workspace.windows())
+    }
+
+    /// Hide the currently active workspace and show the one at `index`
+    pub fn switch_workspace(&mut self, index: usize, space: &mut Space<WindowElement>) {
+        if index == self.active_workspace || index >= self.workspaces.len() {
+            return;
+        }
+
+        self.workspaces[self.active_workspace].unmap(space);
+        self.active_workspace = index;
+        self.workspaces[self.active_workspace].map(space);
+    }
+
+    /// Index of the workspace next to the active one, in the direction of a swipe
+    pub fn swipe_neighbour(&self, progress: f64) -> usize {
+        let offset = if progress >= 0.0 { 1 } else { -1 };
+        (self.active_workspace as isize + offset).rem_euclid(self.workspaces.len() as isize)
+            as usize
+    }
+
+    /// Start a swipe between workspaces, the active workspace is remembered as it is now
+    pub fn begin_swipe(&mut self, space: &mut Space<WindowElement>) {
+        self.workspaces[self.active_workspace].unmap(space);
+    }
+
+    /// Show the active workspace shifted by `progress` output widths,
+    /// with the neighbouring workspace in that direction following it
+    pub fn show_swipe(&self, progress: f64, space: &mut Space<WindowElement>) {
+        let Some(geometry) = space.output_geometry(&self.output) else {
+            return;
+        };
+
+        for workspace in &self.workspaces {
+            workspace.hide(space);
+        }
+
+        let width = geometry.size.w;
+        let shift = -(progress * width as f64).round() as i32;
+
+        self.workspaces[self.active_workspace].map_offset(space, (shift, 0).into());
+
+        if progress != 0.0 {
+            let side = if progress > 0.0 { width } else { -width };
+            self.workspaces[self.swipe_neighbour(progress)]
+                .map_offset(space, (shift + side, 0).into());
+        }
+    }
+
+    /// End a swipe, showing the workspace at `index` as the active one
+    pub fn end_swipe(&mut self, index: usize, space: &mut Space<WindowElement>) {
+        for workspace in &self.workspaces {
+            workspace.hide(space);
+        }
+
+        self.active_workspace = index;
+        self.workspaces[self.active_workspace].map(space);
+    }
+
+    /// Move all windows along with the output, which moved by `offset`
+    pub fn translate(&mut self, offset: Point<i32, Logical>, space: &mut Space<WindowElement>) {
+        self.workspaces[self.active_workspace].unmap(space);
+
+        for workspace in &mut self.workspaces {
+            workspace.translate(offset);
+        }
+
+        self.workspaces[self.active_workspace].map(space);
+    }
+
+    /// Take over the workspaces of a monitor whose output is going away, each workspace is
+    /// merged into the one with the same index, with its windows shifted by `offset`
+    pub fn absorb(
+        &mut self,
+        mut other: Monitor,
+        offset: Point<i32, Logical>,
+        space: &mut Space<WindowElement>,
+    ) {
+        other.workspaces[other.active_workspace].unmap(space);
+
+        for (index, mut workspace) in other.workspaces.into_iter().enumerate() {
+            workspace.translate(offset);
+
+            let index = index.min(self.workspaces.len() - 1);
+            if index == self.active_workspace {
+                workspace.map(space);
+            }
+            self.workspaces[index].append(workspace);
+        }
+    }
 }
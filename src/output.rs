@@ -0,0 +1,357 @@
+use std::sync::Mutex;
+
+use smithay::{
+    output::{Mode, Output, Scale},
+    reexports::wayland_server::{backend::GlobalId, DisplayHandle},
+    utils::{Logical, Point, Transform},
+};
+
+use crate::{
+    backend::Backend, config::CONFIG, monitor::Monitor, state::WallyState,
+    types::output_config::OutputConfig,
+};
+
+/// Requested state of an enabled output, unset fields keep their current value
+#[derive(Debug, Clone, Default)]
+pub struct OutputState {
+    pub mode: Option<Mode>,
+    pub position: Option<Point<i32, Logical>>,
+    pub transform: Option<Transform>,
+    pub scale: Option<f64>,
+}
+
+/// The `wl_output` global of an output, only enabled outputs are advertised to clients
+#[derive(Debug, Default)]
+struct OutputGlobal(Mutex<Option<GlobalId>>);
+
+/// Advertise an output to clients, it is withdrawn again while the output is disabled
+pub fn create_output_global<BackendData: Backend + 'static>(
+    output: &Output,
+    display_handle: &DisplayHandle,
+) {
+    let global = output.create_global::<WallyState<BackendData>>(display_handle);
+
+    let user_data = output.user_data();
+    user_data.insert_if_missing_threadsafe(OutputGlobal::default);
+    *user_data.get::<OutputGlobal>().unwrap().0.lock().unwrap() = Some(global);
+}
+
+fn remove_output_global<BackendData: Backend + 'static>(
+    output: &Output,
+    display_handle: &DisplayHandle,
+) {
+    let global = output
+        .user_data()
+        .get::<OutputGlobal>()
+        .and_then(|global| global.0.lock().unwrap().take());
+
+    if let Some(global) = global {
+        display_handle.remove_global::<WallyState<BackendData>>(global);
+    }
+}
+
+/// The configured mode of an output, picking the highest refresh rate unless one is configured.
+/// A configured refresh rate alone applies to the current size
+fn select_mode(output: &Output, config: &OutputConfig) -> Option<Mode> {
+    if config.mode.is_none() && config.refresh.is_none() {
+        return None;
+    }
+
+    let size = config
+        .mode
+        .or_else(|| output.current_mode().map(|mode| mode.size))?;
+
+    let mode = output
+        .modes()
+        .into_iter()
+        .filter(|mode| mode.size == size)
+        .filter(|mode| config.refresh.is_none_or(|refresh| mode.refresh == refresh))
+        .max_by_key(|mode| mode.refresh);
+
+    if mode.is_none() {
+        tracing::warn!(
+            output = output.name(),
+            ?size,
+            refresh = ?config.refresh,
+            "Configured mode is not available"
+        );
+    }
+
+    mode
+}
+
+impl<BackendData: Backend> WallyState<BackendData> {
+    /// Apply the matching `outputs` sections of the config to an output and map it into the space.
+    /// Returns `false` if the output is disabled, which unmaps it instead
+    pub fn configure_output(&mut self, output: &Output) -> bool {
+        let mut config = OutputConfig::resolve(&CONFIG.outputs, output);
+
+        // outputs the backend can't transform keep the transform they were created with
+        if !BackendData::CONFIGURABLE_OUTPUTS && config.transform.take().is_some() {
+            tracing::warn!(
+                "The transform configured for {} isn't supported by this backend",
+                output.name()
+            );
+        }
+
+        if config.enabled == Some(false) {
+            self.space.unmap_output(output);
+            return false;
+        }
+
+        let mode = select_mode(output, &config);
+        let position = config
+            .position
+            .unwrap_or_else(|| self.next_output_position(output));
+
+        output.change_current_state(
+            mode,
+            config.transform,
+            config.scale.map(Scale::Fractional),
+            Some(position),
+        );
+        if let Some(mode) = mode {
+            output.set_preferred(mode);
+        }
+
+        self.space.map_output(output, position);
+        true
+    }
+
+    /// Unconfigured outputs are placed to the right of the others
+    fn next_output_position(&self, output: &Output) -> Point<i32, Logical> {
+        let x = self
+            .space
+            .outputs()
+            .filter(|other| *other != output)
+            .filter_map(|other| self.space.output_geometry(other))
+            .map(|geometry| geometry.loc.x + geometry.size.w)
+            .max()
+            .unwrap_or(0);
+
+        (x, 0).into()
+    }
+}
+
+impl<BackendData: Backend> WallyState<BackendData> {
+    /// All outputs known to the compositor, with whether they are enabled
+    pub fn outputs(&self) -> impl Iterator<Item = (&Output, bool)> {
+        self.monitors
+            .iter()
+            .map(|monitor| (monitor.output_ref(), true))
+            .chain(self.disabled_outputs.iter().map(|output| (output, false)))
+    }
+
+    fn output_change_valid(&self, output: &Output, change: &OutputState) -> bool {
+        if let Some(mode) = change.mode {
+            if !output.modes().contains(&mode) {
+                return false;
+            }
+            if !BackendData::CONFIGURABLE_OUTPUTS && Some(mode) != output.current_mode() {
+                return false;
+            }
+        }
+
+        if let Some(transform) = change.transform {
+            if !BackendData::CONFIGURABLE_OUTPUTS && transform != output.current_transform() {
+                return false;
+            }
+        }
+
+        change.scale.is_none_or(|scale| scale > 0.0)
+    }
+
+    /// Apply a new state to a set of outputs all at once, or only check whether it could be
+    /// applied. Outputs mapped to `None` are disabled, their workspaces move to the first
+    /// output that stays enabled. Returns `false`, changing nothing, if the state is invalid
+    pub fn apply_output_changes(
+        &mut self,
+        changes: Vec<(Output, Option<OutputState>)>,
+        test_only: bool,
+    ) -> bool {
+        let known = changes
+            .iter()
+            .all(|(output, _)| self.outputs().any(|(known, _)| known == output));
+        let valid = changes.iter().all(|(output, change)| {
+            change
+                .as_ref()
+                .is_none_or(|change| self.output_change_valid(output, change))
+        });
+        // there always has to be an output to render to
+        let any_enabled = self.outputs().any(|(output, enabled)| {
+            changes
+                .iter()
+                .find(|(changed, _)| changed == output)
+                .map_or(enabled, |(_, change)| change.is_some())
+        });
+
+        if !known || !valid || !any_enabled {
+            return false;
+        }
+        if test_only {
+            return true;
+        }
+
+        // workspaces are about to move, and monitor indices to change
+        self.finish_workspace_swipe();
+
+        for (output, change) in &changes {
+            let Some(change) = change else {
+                continue;
+            };
+
+            if let Some(index) = self.disabled_outputs.iter().position(|o| o == output) {
+                self.disabled_outputs.remove(index);
+                self.monitors.push(Monitor::new(output.clone()));
+                create_output_global::<BackendData>(output, &self.display_handle);
+            }
+
+            let old_location = output.current_location();
+            let location = change.position.unwrap_or(old_location);
+
+            output.change_current_state(
+                change.mode,
+                change.transform,
+                change.scale.map(Scale::Fractional),
+                Some(location),
+            );
+            self.space.map_output(output, location);
+
+            if location != old_location {
+                if let Some(monitor) = self
+                    .monitors
+                    .iter_mut()
+                    .find(|monitor| monitor.output_ref() == output)
+                {
+                    monitor.translate(location - old_location, &mut self.space);
+                }
+            }
+
+            self.backend_data.reset_buffers(output);
+        }
+
+        for (output, _) in changes.iter().filter(|(_, change)| change.is_none()) {
+            self.disable_output(output);
+        }
+
+        self.configure_lock_surfaces();
+        self.output_configuration_changed();
+        self.refresh_pointer_focus();
+        true
+    }
+
+    fn disable_output(&mut self, output: &Output) {
+        let Some(index) = self
+            .monitors
+            .iter()
+            .position(|monitor| monitor.output_ref() == output)
+        else {
+            return;
+        };
+
+        let monitor = self.monitors.remove(index);
+        self.space.unmap_output(output);
+        remove_output_global::<BackendData>(output, &self.display_handle);
+        self.disabled_outputs.push(output.clone());
+
+        let Some(target) = self.monitors.first_mut() else {
+            return;
+        };
+        let offset = target.output_ref().current_location() - output.current_location();
+        target.absorb(monitor, offset, &mut self.space);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use smithay::output::{PhysicalProperties, Subpixel};
+
+    use super::*;
+
+    fn mode(w: i32, h: i32, refresh: i32) -> Mode {
+        Mode {
+            size: (w, h).into(),
+            refresh,
+        }
+    }
+
+    fn output(modes: &[Mode], current: Mode) -> Output {
+        let output = Output::new(
+            "DP-1".into(),
+            PhysicalProperties {
+                size: (0, 0).into(),
+                subpixel: Subpixel::Unknown,
+                make: "Dell".into(),
+                model: "U2720Q".into(),
+            },
+        );
+        for mode in modes {
+            output.add_mode(*mode);
+        }
+        // a scale of 2 halves the logical size, the configured mode is in pixels regardless
+        output.change_current_state(Some(current), None, Some(Scale::Integer(2)), None);
+        output
+    }
+
+    #[test]
+    fn nothing_configured_keeps_the_mode() {
+        let output = output(&[mode(1920, 1080, 60_000)], mode(1920, 1080, 60_000));
+
+        assert_eq!(select_mode(&output, &OutputConfig::name("DP-1")), None);
+    }
+
+    #[test]
+    fn highest_refresh_rate_is_picked() {
+        let modes = [
+            mode(3840, 2160, 30_000),
+            mode(3840, 2160, 60_000),
+            mode(1920, 1080, 144_000),
+        ];
+        let output = output(&modes, mode(1920, 1080, 144_000));
+        let config = OutputConfig {
+            mode: Some((3840, 2160).into()),
+            ..OutputConfig::name("DP-1")
+        };
+
+        assert_eq!(
+            select_mode(&output, &config),
+            Some(mode(3840, 2160, 60_000))
+        );
+    }
+
+    #[test]
+    fn refresh_alone_applies_to_the_current_size() {
+        let modes = [
+            mode(3840, 2160, 60_000),
+            mode(1920, 1080, 60_000),
+            mode(1920, 1080, 120_000),
+        ];
+        let output = output(&modes, mode(1920, 1080, 120_000));
+        let config = OutputConfig {
+            refresh: Some(60_000),
+            ..OutputConfig::name("DP-1")
+        };
+
+        assert_eq!(
+            select_mode(&output, &config),
+            Some(mode(1920, 1080, 60_000))
+        );
+    }
+
+    #[test]
+    fn unavailable_modes_are_not_picked() {
+        let output = output(&[mode(3840, 2160, 60_000)], mode(3840, 2160, 60_000));
+        let halved = OutputConfig {
+            mode: Some((1920, 1080).into()),
+            ..OutputConfig::name("DP-1")
+        };
+        let refresh = OutputConfig {
+            mode: Some((3840, 2160).into()),
+            refresh: Some(144_000),
+            ..OutputConfig::name("DP-1")
+        };
+
+        assert_eq!(select_mode(&output, &halved), None);
+        assert_eq!(select_mode(&output, &refresh), None);
+    }
+}
@@ -0,0 +1,464 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+use smithay::{
+    output::Output,
+    reexports::{
+        wayland_protocols::ext::{
+            image_capture_source::v1::server::{
+                ext_image_capture_source_v1::{self, ExtImageCaptureSourceV1},
+                ext_output_image_capture_source_manager_v1::{
+                    self, ExtOutputImageCaptureSourceManagerV1,
+                },
+            },
+            image_copy_capture::v1::server::{
+                ext_image_copy_capture_cursor_session_v1::{
+                    self, ExtImageCopyCaptureCursorSessionV1,
+                },
+                ext_image_copy_capture_frame_v1::{self, ExtImageCopyCaptureFrameV1},
+                ext_image_copy_capture_manager_v1::{self, ExtImageCopyCaptureManagerV1},
+                ext_image_copy_capture_session_v1::{self, ExtImageCopyCaptureSessionV1},
+            },
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            protocol::{wl_buffer::WlBuffer, wl_shm},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+        },
+    },
+    utils::{Physical, Rectangle, Size},
+};
+
+use crate::{
+    backend::Backend,
+    capture::{self, CaptureFrame, CaptureRequest, CAPTURE_FORMAT},
+    state::WallyState,
+};
+
+const VERSION: u32 = 1;
+
+/// State of the `ext_image_copy_capture_manager_v1` and
+/// `ext_output_image_capture_source_manager_v1` globals
+#[derive(Debug)]
+pub struct ImageCopyCaptureState {
+    _capture_global: GlobalId,
+    _output_source_global: GlobalId,
+    /// Sessions capturing an output, with the buffer size last advertised to them
+    sessions: Vec<(ExtImageCopyCaptureSessionV1, Size<i32, Physical>)>,
+}
+
+impl ImageCopyCaptureState {
+    pub fn new<BackendData: Backend + 'static>(display_handle: &DisplayHandle) -> Self {
+        let capture_global = display_handle
+            .create_global::<WallyState<BackendData>, ExtImageCopyCaptureManagerV1, ()>(
+                VERSION,
+                (),
+            );
+        let output_source_global = display_handle
+            .create_global::<WallyState<BackendData>, ExtOutputImageCaptureSourceManagerV1, ()>(
+                VERSION,
+                (),
+            );
+
+        Self {
+            _capture_global: capture_global,
+            _output_source_global: output_source_global,
+            sessions: Vec::new(),
+        }
+    }
+}
+
+pub struct ImageCaptureSourceData {
+    output: Option<Output>,
+}
+
+pub struct ImageCopySessionData {
+    /// `None` for sessions that can never produce frames
+    output: Option<Output>,
+    paint_cursors: bool,
+    frame: Mutex<Option<ExtImageCopyCaptureFrameV1>>,
+}
+
+pub struct ImageCopyFrameData {
+    session: ExtImageCopyCaptureSessionV1,
+    buffer: Mutex<Option<WlBuffer>>,
+    captured: AtomicBool,
+}
+
+/// Advertise the buffer constraints of a session, these must be met by the buffers of its frames
+fn send_constraints<BackendData: Backend>(
+    state: &WallyState<BackendData>,
+    session: &ExtImageCopyCaptureSessionV1,
+    size: Size<i32, Physical>,
+) {
+    session.buffer_size(size.w as u32, size.h as u32);
+    session.shm_format(wl_shm::Format::Argb8888);
+
+    if let Some((device, modifiers)) = state.capture_state.dmabuf() {
+        session.dmabuf_device(device.to_ne_bytes().to_vec());
+        session.dmabuf_format(
+            CAPTURE_FORMAT as u32,
+            modifiers
+                .iter()
+                .flat_map(|modifier| modifier.to_ne_bytes())
+                .collect(),
+        );
+    }
+
+    session.done();
+}
+
+/// Region of an output a session captures, `None` if the output is disabled
+fn session_region<BackendData: Backend>(
+    state: &WallyState<BackendData>,
+    output: &Output,
+) -> Option<Rectangle<i32, Physical>> {
+    state.space.output_geometry(output)?;
+    capture::output_region(output, None)
+}
+
+impl<BackendData: Backend + 'static> WallyState<BackendData> {
+    /// Advertise new buffer constraints to sessions whose output changed size,
+    /// and stop the ones whose output is gone or disabled
+    pub fn refresh_capture_sessions(&mut self) {
+        let sessions = std::mem::take(&mut self.image_copy_capture_state.sessions);
+
+        for (session, size) in sessions {
+            let region = session
+                .data::<ImageCopySessionData>()
+                .and_then(|data| data.output.as_ref())
+                .and_then(|output| session_region(self, output));
+
+            let Some(region) = region else {
+                session.stopped();
+                continue;
+            };
+
+            if region.size != size {
+                send_constraints(self, &session, region.size);
+            }
+            self.image_copy_capture_state
+                .sessions
+                .push((session, region.size));
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> GlobalDispatch<ExtOutputImageCaptureSourceManagerV1, ()>
+    for WallyState<BackendData>
+{
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtOutputImageCaptureSourceManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ExtOutputImageCaptureSourceManagerV1, ()>
+    for WallyState<BackendData>
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _manager: &ExtOutputImageCaptureSourceManagerV1,
+        request: ext_output_image_capture_source_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_output_image_capture_source_manager_v1::Request::CreateSource {
+                source,
+                output,
+            } => {
+                data_init.init(
+                    source,
+                    ImageCaptureSourceData {
+                        output: Output::from_resource(&output),
+                    },
+                );
+            }
+            ext_output_image_capture_source_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ExtImageCaptureSourceV1, ImageCaptureSourceData>
+    for WallyState<BackendData>
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _source: &ExtImageCaptureSourceV1,
+        request: ext_image_capture_source_v1::Request,
+        _data: &ImageCaptureSourceData,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_image_capture_source_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> GlobalDispatch<ExtImageCopyCaptureManagerV1, ()>
+    for WallyState<BackendData>
+{
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtImageCopyCaptureManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ExtImageCopyCaptureManagerV1, ()>
+    for WallyState<BackendData>
+{
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        manager: &ExtImageCopyCaptureManagerV1,
+        request: ext_image_copy_capture_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_image_copy_capture_manager_v1::Request::CreateSession {
+                session,
+                source,
+                options,
+            } => {
+                let paint_cursors = match options {
+                    WEnum::Value(options) => {
+                        options.contains(ext_image_copy_capture_manager_v1::Options::PaintCursors)
+                    }
+                    WEnum::Unknown(_) => {
+                        manager.post_error(
+                            ext_image_copy_capture_manager_v1::Error::InvalidOption,
+                            "unknown capture options",
+                        );
+                        return;
+                    }
+                };
+
+                let output = source
+                    .data::<ImageCaptureSourceData>()
+                    .and_then(|data| data.output.clone());
+
+                let session = data_init.init(
+                    session,
+                    ImageCopySessionData {
+                        output: output.clone(),
+                        paint_cursors,
+                        frame: Mutex::new(None),
+                    },
+                );
+
+                match output.and_then(|output| session_region(state, &output)) {
+                    Some(region) => {
+                        send_constraints(state, &session, region.size);
+                        state
+                            .image_copy_capture_state
+                            .sessions
+                            .push((session, region.size));
+                    }
+                    None => session.stopped(),
+                }
+            }
+            ext_image_copy_capture_manager_v1::Request::CreatePointerCursorSession {
+                session,
+                ..
+            } => {
+                data_init.init(session, ());
+            }
+            ext_image_copy_capture_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ExtImageCopyCaptureCursorSessionV1, ()>
+    for WallyState<BackendData>
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _cursor_session: &ExtImageCopyCaptureCursorSessionV1,
+        request: ext_image_copy_capture_cursor_session_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_image_copy_capture_cursor_session_v1::Request::GetCaptureSession { session } => {
+                // cursor capture is not supported, clients can fall back to
+                // sessions with painted cursors instead
+                let session = data_init.init(
+                    session,
+                    ImageCopySessionData {
+                        output: None,
+                        paint_cursors: false,
+                        frame: Mutex::new(None),
+                    },
+                );
+                session.stopped();
+            }
+            ext_image_copy_capture_cursor_session_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ExtImageCopyCaptureSessionV1, ImageCopySessionData>
+    for WallyState<BackendData>
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        session: &ExtImageCopyCaptureSessionV1,
+        request: ext_image_copy_capture_session_v1::Request,
+        data: &ImageCopySessionData,
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_image_copy_capture_session_v1::Request::CreateFrame { frame } => {
+                let mut current_frame = data.frame.lock().unwrap();
+
+                if current_frame.as_ref().is_some_and(|frame| frame.is_alive()) {
+                    session.post_error(
+                        ext_image_copy_capture_session_v1::Error::DuplicateFrame,
+                        "the previous frame has not been destroyed",
+                    );
+                    return;
+                }
+
+                let frame = data_init.init(
+                    frame,
+                    ImageCopyFrameData {
+                        session: session.clone(),
+                        buffer: Mutex::new(None),
+                        captured: AtomicBool::new(false),
+                    },
+                );
+
+                *current_frame = Some(frame);
+            }
+            ext_image_copy_capture_session_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        session: &ExtImageCopyCaptureSessionV1,
+        _data: &ImageCopySessionData,
+    ) {
+        state.capture_state.remove_session(&session.id());
+        state
+            .image_copy_capture_state
+            .sessions
+            .retain(|(known, _)| known != session);
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ExtImageCopyCaptureFrameV1, ImageCopyFrameData>
+    for WallyState<BackendData>
+{
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        frame: &ExtImageCopyCaptureFrameV1,
+        request: ext_image_copy_capture_frame_v1::Request,
+        data: &ImageCopyFrameData,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_image_copy_capture_frame_v1::Request::AttachBuffer { buffer } => {
+                *data.buffer.lock().unwrap() = Some(buffer);
+            }
+            ext_image_copy_capture_frame_v1::Request::DamageBuffer {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                // frames are always rendered in full, so buffer damage is only validated
+                if x < 0 || y < 0 || width <= 0 || height <= 0 {
+                    frame.post_error(
+                        ext_image_copy_capture_frame_v1::Error::InvalidBufferDamage,
+                        "invalid buffer damage",
+                    );
+                }
+            }
+            ext_image_copy_capture_frame_v1::Request::Capture => {
+                if data.captured.swap(true, Ordering::SeqCst) {
+                    frame.post_error(
+                        ext_image_copy_capture_frame_v1::Error::AlreadyCaptured,
+                        "frame was already captured",
+                    );
+                    return;
+                }
+
+                let Some(buffer) = data.buffer.lock().unwrap().clone() else {
+                    frame.post_error(
+                        ext_image_copy_capture_frame_v1::Error::NoBuffer,
+                        "no buffer attached",
+                    );
+                    return;
+                };
+
+                let session_data = data.session.data::<ImageCopySessionData>().unwrap();
+
+                let target = session_data
+                    .output
+                    .clone()
+                    .filter(|_| data.session.is_alive())
+                    .and_then(|output| {
+                        session_region(state, &output).map(|region| (output, region))
+                    });
+
+                let Some((output, region)) = target else {
+                    frame.failed(ext_image_copy_capture_frame_v1::FailureReason::Stopped);
+                    return;
+                };
+
+                if !capture::buffer_matches(&buffer, region.size) {
+                    frame.failed(ext_image_copy_capture_frame_v1::FailureReason::BufferConstraints);
+                    return;
+                }
+
+                // the first capture of a session always has full damage,
+                // so frames can always wait for damage
+                state.capture_state.queue(CaptureRequest {
+                    output,
+                    region,
+                    buffer,
+                    paint_cursor: session_data.paint_cursors,
+                    wait_for_damage: true,
+                    session: data.session.id(),
+                    frame: CaptureFrame::ImageCopy(frame.clone()),
+                });
+            }
+            ext_image_copy_capture_frame_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod image_copy_capture;
+pub mod output_management;
+pub mod screencopy;
+pub mod virtual_keyboard;
+pub mod virtual_pointer;
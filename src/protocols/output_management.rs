@@ -0,0 +1,654 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+use smithay::{
+    output::{Mode, Output, WeakOutput},
+    reexports::{
+        wayland_protocols_wlr::output_management::v1::server::{
+            zwlr_output_configuration_head_v1::{self, ZwlrOutputConfigurationHeadV1},
+            zwlr_output_configuration_v1::{self, ZwlrOutputConfigurationV1},
+            zwlr_output_head_v1::{self, AdaptiveSyncState, ZwlrOutputHeadV1},
+            zwlr_output_manager_v1::{self, ZwlrOutputManagerV1},
+            zwlr_output_mode_v1::{self, ZwlrOutputModeV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+        },
+    },
+    utils::{Logical, Point, Size, Transform},
+};
+
+use crate::{
+    backend::Backend, output::OutputState, state::WallyState, types::output_config::OutputSerial,
+};
+
+const VERSION: u32 = 4;
+
+/// State of the `zwlr_output_manager_v1` global
+#[derive(Debug)]
+pub struct OutputManagementState {
+    _global: GlobalId,
+    /// Bumped whenever outputs change, configurations made for an older serial are cancelled
+    serial: u32,
+    managers: Vec<ManagerData>,
+}
+
+impl OutputManagementState {
+    pub fn new<BackendData: Backend + 'static>(display_handle: &DisplayHandle) -> Self {
+        let global = display_handle
+            .create_global::<WallyState<BackendData>, ZwlrOutputManagerV1, ()>(VERSION, ());
+
+        Self {
+            _global: global,
+            serial: 0,
+            managers: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ManagerData {
+    manager: ZwlrOutputManagerV1,
+    heads: Vec<HeadData>,
+}
+
+/// Heads and modes released by the client stay known, so they aren't advertised again.
+/// Nothing is sent to them anymore
+#[derive(Debug)]
+struct HeadData {
+    output: Output,
+    head: ZwlrOutputHeadV1,
+    modes: Vec<(Mode, ZwlrOutputModeV1)>,
+    /// State last sent to the client, only changes are sent
+    sent: Option<HeadState>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct HeadState {
+    enabled: bool,
+    mode: Option<Mode>,
+    position: Point<i32, Logical>,
+    transform: Transform,
+    scale: f64,
+}
+
+impl HeadState {
+    fn of(output: &Output, enabled: bool) -> Self {
+        Self {
+            enabled,
+            mode: output.current_mode(),
+            position: output.current_location(),
+            transform: output.current_transform(),
+            scale: output.current_scale().fractional_scale(),
+        }
+    }
+}
+
+pub struct ConfigurationData {
+    serial: u32,
+    /// Heads in the order they were configured, `None` for the ones to disable
+    heads: Mutex<Vec<(ZwlrOutputHeadV1, Option<ZwlrOutputConfigurationHeadV1>)>>,
+    used: AtomicBool,
+}
+
+pub struct ConfigurationHeadData {
+    head: ZwlrOutputHeadV1,
+    pending: Mutex<PendingHead>,
+}
+
+impl ConfigurationHeadData {
+    fn output(&self) -> Option<Output> {
+        self.head.data::<WeakOutput>()?.upgrade()
+    }
+}
+
+#[derive(Debug, Default)]
+struct PendingHead {
+    state: OutputState,
+    mode_set: bool,
+    adaptive_sync_set: bool,
+    /// A custom mode the output doesn't have or adaptive sync was requested,
+    /// which fails the configuration once applied
+    unsupported: bool,
+}
+
+/// Advertise an output to a manager
+fn new_head<BackendData: Backend + 'static>(
+    display_handle: &DisplayHandle,
+    client: &Client,
+    manager: &ZwlrOutputManagerV1,
+    output: &Output,
+    enabled: bool,
+) -> Option<HeadData> {
+    let head = client
+        .create_resource::<ZwlrOutputHeadV1, _, WallyState<BackendData>>(
+            display_handle,
+            manager.version(),
+            output.downgrade(),
+        )
+        .ok()?;
+    manager.head(&head);
+
+    let properties = output.physical_properties();
+    head.name(output.name());
+    head.description(output.description());
+    if properties.size.w > 0 && properties.size.h > 0 {
+        head.physical_size(properties.size.w, properties.size.h);
+    }
+
+    if head.version() >= zwlr_output_head_v1::EVT_MAKE_SINCE {
+        head.make(properties.make);
+        head.model(properties.model);
+        if let Some(serial) = output.user_data().get::<OutputSerial>() {
+            head.serial_number(serial.0.clone());
+        }
+    }
+
+    if head.version() >= zwlr_output_head_v1::EVT_ADAPTIVE_SYNC_SINCE {
+        head.adaptive_sync(AdaptiveSyncState::Disabled);
+    }
+
+    let mut head = HeadData {
+        output: output.clone(),
+        head,
+        modes: Vec::new(),
+        sent: None,
+    };
+    sync_head::<BackendData>(display_handle, client, &mut head, enabled);
+
+    Some(head)
+}
+
+/// Send the modes and state of an output that changed since they were last sent
+fn sync_head<BackendData: Backend + 'static>(
+    display_handle: &DisplayHandle,
+    client: &Client,
+    head: &mut HeadData,
+    enabled: bool,
+) {
+    let modes = head.output.modes();
+    let preferred = head.output.preferred_mode();
+
+    head.modes.retain(|(mode, resource)| {
+        let keep = modes.contains(mode);
+        if !keep && resource.is_alive() {
+            resource.finished();
+        }
+        keep
+    });
+
+    if !head.head.is_alive() {
+        return;
+    }
+
+    // modes have to be advertised before they can be the current one
+    for mode in modes {
+        if head.modes.iter().any(|(known, _)| *known == mode) {
+            continue;
+        }
+
+        let Ok(resource) = client.create_resource::<ZwlrOutputModeV1, _, WallyState<BackendData>>(
+            display_handle,
+            head.head.version(),
+            mode,
+        ) else {
+            continue;
+        };
+        head.head.mode(&resource);
+
+        resource.size(mode.size.w, mode.size.h);
+        if mode.refresh > 0 {
+            resource.refresh(mode.refresh);
+        }
+        if preferred == Some(mode) {
+            resource.preferred();
+        }
+
+        head.modes.push((mode, resource));
+    }
+
+    let state = HeadState::of(&head.output, enabled);
+    let sent = head.sent.take();
+
+    if sent.as_ref().map(|sent| sent.enabled) != Some(state.enabled) {
+        head.head.enabled(state.enabled as i32);
+    }
+
+    if state.enabled {
+        // everything is sent again once a head is enabled
+        let sent = sent.filter(|sent| sent.enabled);
+
+        if sent.as_ref().map(|sent| sent.mode) != Some(state.mode) {
+            let current = head
+                .modes
+                .iter()
+                .filter(|(_, resource)| resource.is_alive())
+                .find(|(mode, _)| Some(*mode) == state.mode);
+            if let Some((_, resource)) = current {
+                head.head.current_mode(resource);
+            }
+        }
+        if sent.as_ref().map(|sent| sent.position) != Some(state.position) {
+            head.head.position(state.position.x, state.position.y);
+        }
+        if sent.as_ref().map(|sent| sent.transform) != Some(state.transform) {
+            head.head.transform(state.transform.into());
+        }
+        if sent.as_ref().map(|sent| sent.scale) != Some(state.scale) {
+            head.head.scale(state.scale);
+        }
+    }
+
+    head.sent = Some(state);
+}
+
+impl<BackendData: Backend + 'static> WallyState<BackendData> {
+    /// Let output management clients and capture sessions know about added, removed or changed outputs
+    pub fn output_configuration_changed(&mut self) {
+        self.refresh_capture_sessions();
+
+        let outputs: Vec<(Output, bool)> = self
+            .outputs()
+            .map(|(output, enabled)| (output.clone(), enabled))
+            .collect();
+
+        let state = &mut self.output_management_state;
+        state.serial = state.serial.wrapping_add(1);
+
+        for manager in &mut state.managers {
+            let Some(client) = manager.manager.client() else {
+                continue;
+            };
+
+            manager.heads.retain(|head| {
+                let keep = outputs.iter().any(|(output, _)| *output == head.output);
+                if !keep {
+                    for (_, mode) in head.modes.iter().filter(|(_, mode)| mode.is_alive()) {
+                        mode.finished();
+                    }
+                    if head.head.is_alive() {
+                        head.head.finished();
+                    }
+                }
+                keep
+            });
+
+            for (output, enabled) in &outputs {
+                match manager.heads.iter().position(|head| head.output == *output) {
+                    Some(index) => sync_head::<BackendData>(
+                        &self.display_handle,
+                        &client,
+                        &mut manager.heads[index],
+                        *enabled,
+                    ),
+                    None => manager.heads.extend(new_head::<BackendData>(
+                        &self.display_handle,
+                        &client,
+                        &manager.manager,
+                        output,
+                        *enabled,
+                    )),
+                }
+            }
+
+            manager.manager.done(state.serial);
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> GlobalDispatch<ZwlrOutputManagerV1, ()>
+    for WallyState<BackendData>
+{
+    fn bind(
+        state: &mut Self,
+        handle: &DisplayHandle,
+        client: &Client,
+        resource: New<ZwlrOutputManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let manager = data_init.init(resource, ());
+
+        let heads = state
+            .outputs()
+            .filter_map(|(output, enabled)| {
+                new_head::<BackendData>(handle, client, &manager, output, enabled)
+            })
+            .collect();
+        manager.done(state.output_management_state.serial);
+
+        state
+            .output_management_state
+            .managers
+            .push(ManagerData { manager, heads });
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ZwlrOutputManagerV1, ()> for WallyState<BackendData> {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        manager: &ZwlrOutputManagerV1,
+        request: zwlr_output_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zwlr_output_manager_v1::Request::CreateConfiguration { id, serial } => {
+                data_init.init(
+                    id,
+                    ConfigurationData {
+                        serial,
+                        heads: Mutex::new(Vec::new()),
+                        used: AtomicBool::new(false),
+                    },
+                );
+            }
+            zwlr_output_manager_v1::Request::Stop => {
+                state
+                    .output_management_state
+                    .managers
+                    .retain(|data| data.manager != *manager);
+                manager.finished();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut Self, _client: ClientId, manager: &ZwlrOutputManagerV1, _data: &()) {
+        state
+            .output_management_state
+            .managers
+            .retain(|data| data.manager != *manager);
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ZwlrOutputHeadV1, WeakOutput>
+    for WallyState<BackendData>
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _head: &ZwlrOutputHeadV1,
+        request: zwlr_output_head_v1::Request,
+        _data: &WeakOutput,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zwlr_output_head_v1::Request::Release => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ZwlrOutputModeV1, Mode> for WallyState<BackendData> {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _mode: &ZwlrOutputModeV1,
+        request: zwlr_output_mode_v1::Request,
+        _data: &Mode,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zwlr_output_mode_v1::Request::Release => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ZwlrOutputConfigurationV1, ConfigurationData>
+    for WallyState<BackendData>
+{
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        configuration: &ZwlrOutputConfigurationV1,
+        request: zwlr_output_configuration_v1::Request,
+        data: &ConfigurationData,
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let (head, config_head) = match request {
+            zwlr_output_configuration_v1::Request::EnableHead { id, head } => {
+                let config_head = data_init.init(
+                    id,
+                    ConfigurationHeadData {
+                        head: head.clone(),
+                        pending: Mutex::new(PendingHead::default()),
+                    },
+                );
+                (head, Some(config_head))
+            }
+            zwlr_output_configuration_v1::Request::DisableHead { head } => (head, None),
+            zwlr_output_configuration_v1::Request::Apply => {
+                apply_configuration(state, configuration, data, false);
+                return;
+            }
+            zwlr_output_configuration_v1::Request::Test => {
+                apply_configuration(state, configuration, data, true);
+                return;
+            }
+            zwlr_output_configuration_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        if data.used.load(Ordering::SeqCst) {
+            configuration.post_error(
+                zwlr_output_configuration_v1::Error::AlreadyUsed,
+                "configuration has already been applied or tested",
+            );
+            return;
+        }
+
+        let mut heads = data.heads.lock().unwrap();
+        if heads.iter().any(|(configured, _)| *configured == head) {
+            configuration.post_error(
+                zwlr_output_configuration_v1::Error::AlreadyConfiguredHead,
+                "head has already been configured",
+            );
+            return;
+        }
+
+        heads.push((head, config_head));
+    }
+}
+
+fn apply_configuration<BackendData: Backend + 'static>(
+    state: &mut WallyState<BackendData>,
+    configuration: &ZwlrOutputConfigurationV1,
+    data: &ConfigurationData,
+    test_only: bool,
+) {
+    if data.used.swap(true, Ordering::SeqCst) {
+        configuration.post_error(
+            zwlr_output_configuration_v1::Error::AlreadyUsed,
+            "configuration has already been applied or tested",
+        );
+        return;
+    }
+
+    // the client configured outputs that have changed since
+    if data.serial != state.output_management_state.serial {
+        configuration.cancelled();
+        return;
+    }
+
+    let mut changes = Vec::new();
+    let mut unsupported = false;
+
+    for (head, config_head) in data.heads.lock().unwrap().iter() {
+        let Some(output) = head
+            .data::<WeakOutput>()
+            .and_then(|output| output.upgrade())
+        else {
+            configuration.cancelled();
+            return;
+        };
+
+        let change = config_head.as_ref().map(|config_head| {
+            let pending = config_head
+                .data::<ConfigurationHeadData>()
+                .unwrap()
+                .pending
+                .lock()
+                .unwrap();
+            unsupported |= pending.unsupported;
+            pending.state.clone()
+        });
+
+        changes.push((output, change));
+    }
+
+    let unconfigured = state
+        .outputs()
+        .any(|(output, _)| !changes.iter().any(|(configured, _)| configured == output));
+    if unconfigured {
+        configuration.post_error(
+            zwlr_output_configuration_v1::Error::UnconfiguredHead,
+            "not all heads have been configured",
+        );
+        return;
+    }
+
+    if !unsupported && state.apply_output_changes(changes, test_only) {
+        configuration.succeeded();
+    } else {
+        configuration.failed();
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ZwlrOutputConfigurationHeadV1, ConfigurationHeadData>
+    for WallyState<BackendData>
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        config_head: &ZwlrOutputConfigurationHeadV1,
+        request: zwlr_output_configuration_head_v1::Request,
+        data: &ConfigurationHeadData,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        let mut pending = data.pending.lock().unwrap();
+
+        let already_set = match &request {
+            zwlr_output_configuration_head_v1::Request::SetMode { .. }
+            | zwlr_output_configuration_head_v1::Request::SetCustomMode { .. } => pending.mode_set,
+            zwlr_output_configuration_head_v1::Request::SetPosition { .. } => {
+                pending.state.position.is_some()
+            }
+            zwlr_output_configuration_head_v1::Request::SetTransform { .. } => {
+                pending.state.transform.is_some()
+            }
+            zwlr_output_configuration_head_v1::Request::SetScale { .. } => {
+                pending.state.scale.is_some()
+            }
+            zwlr_output_configuration_head_v1::Request::SetAdaptiveSync { .. } => {
+                pending.adaptive_sync_set
+            }
+            _ => false,
+        };
+        if already_set {
+            config_head.post_error(
+                zwlr_output_configuration_head_v1::Error::AlreadySet,
+                "property has already been set",
+            );
+            return;
+        }
+
+        // the output is gone, applying the configuration cancels it
+        let Some(output) = data.output() else {
+            return;
+        };
+
+        match request {
+            zwlr_output_configuration_head_v1::Request::SetMode { mode } => {
+                let mode = mode.data::<Mode>().copied();
+                if !mode.is_some_and(|mode| output.modes().contains(&mode)) {
+                    config_head.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidMode,
+                        "mode doesn't belong to head",
+                    );
+                    return;
+                }
+
+                pending.state.mode = mode;
+                pending.mode_set = true;
+            }
+            zwlr_output_configuration_head_v1::Request::SetCustomMode {
+                width,
+                height,
+                refresh,
+            } => {
+                if width <= 0 || height <= 0 || refresh < 0 {
+                    config_head.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidCustomMode,
+                        "mode is invalid",
+                    );
+                    return;
+                }
+
+                // only modes the output advertises can be set, a refresh rate of zero picks the highest
+                let mode = output
+                    .modes()
+                    .into_iter()
+                    .filter(|mode| mode.size == Size::from((width, height)))
+                    .filter(|mode| refresh == 0 || mode.refresh == refresh)
+                    .max_by_key(|mode| mode.refresh);
+
+                pending.unsupported |= mode.is_none();
+                pending.state.mode = mode;
+                pending.mode_set = true;
+            }
+            zwlr_output_configuration_head_v1::Request::SetPosition { x, y } => {
+                pending.state.position = Some((x, y).into());
+            }
+            zwlr_output_configuration_head_v1::Request::SetTransform { transform } => {
+                let WEnum::Value(transform) = transform else {
+                    config_head.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidTransform,
+                        "transform value outside enum",
+                    );
+                    return;
+                };
+
+                pending.state.transform = Some(transform.into());
+            }
+            zwlr_output_configuration_head_v1::Request::SetScale { scale } => {
+                if scale <= 0.0 {
+                    config_head.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidScale,
+                        "scale negative or zero",
+                    );
+                    return;
+                }
+
+                pending.state.scale = Some(scale);
+            }
+            zwlr_output_configuration_head_v1::Request::SetAdaptiveSync { state } => {
+                match state {
+                    WEnum::Value(AdaptiveSyncState::Disabled) => {}
+                    // none of the backends can enable it
+                    WEnum::Value(AdaptiveSyncState::Enabled) => pending.unsupported = true,
+                    _ => {
+                        config_head.post_error(
+                            zwlr_output_configuration_head_v1::Error::InvalidAdaptiveSyncState,
+                            "invalid adaptive sync state",
+                        );
+                        return;
+                    }
+                }
+
+                pending.adaptive_sync_set = true;
+            }
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use smithay::{
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::screencopy::v1::server::{
+            zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+            zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId, ObjectId},
+            protocol::wl_shm,
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    utils::{Logical, Physical, Rectangle},
+};
+
+use crate::{
+    backend::Backend,
+    capture::{self, CaptureFrame, CaptureRequest, CAPTURE_FORMAT},
+    state::WallyState,
+};
+
+const VERSION: u32 = 3;
+
+/// State of the `zwlr_screencopy_manager_v1` global
+#[derive(Debug)]
+pub struct ScreencopyManagerState {
+    _global: GlobalId,
+}
+
+impl ScreencopyManagerState {
+    pub fn new<BackendData: Backend + 'static>(display_handle: &DisplayHandle) -> Self {
+        let global = display_handle
+            .create_global::<WallyState<BackendData>, ZwlrScreencopyManagerV1, ()>(VERSION, ());
+
+        Self { _global: global }
+    }
+}
+
+pub struct ScreencopyFrameData {
+    /// Damage is tracked per manager, which is what clients reuse between frames
+    manager: ObjectId,
+    /// `None` when the requested output or region can not be captured
+    target: Option<(Output, Rectangle<i32, Physical>)>,
+    overlay_cursor: bool,
+    used: AtomicBool,
+}
+
+impl<BackendData: Backend + 'static> GlobalDispatch<ZwlrScreencopyManagerV1, ()>
+    for WallyState<BackendData>
+{
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrScreencopyManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ZwlrScreencopyManagerV1, ()>
+    for WallyState<BackendData>
+{
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        manager: &ZwlrScreencopyManagerV1,
+        request: zwlr_screencopy_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let (frame, overlay_cursor, output, region) = match request {
+            zwlr_screencopy_manager_v1::Request::CaptureOutput {
+                frame,
+                overlay_cursor,
+                output,
+            } => (frame, overlay_cursor, output, None),
+            zwlr_screencopy_manager_v1::Request::CaptureOutputRegion {
+                frame,
+                overlay_cursor,
+                output,
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let region = Rectangle::<i32, Logical>::new((x, y).into(), (width, height).into());
+                (frame, overlay_cursor, output, Some(region))
+            }
+            zwlr_screencopy_manager_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        let target = Output::from_resource(&output).and_then(|output| {
+            capture::output_region(&output, region).map(|region| (output, region))
+        });
+
+        let frame = data_init.init(
+            frame,
+            ScreencopyFrameData {
+                manager: manager.id(),
+                target: target.clone(),
+                overlay_cursor: overlay_cursor != 0,
+                used: AtomicBool::new(false),
+            },
+        );
+
+        let Some((_, region)) = target else {
+            frame.failed();
+            return;
+        };
+
+        let (width, height) = (region.size.w as u32, region.size.h as u32);
+
+        frame.buffer(wl_shm::Format::Argb8888, width, height, width * 4);
+
+        if frame.version() >= 3 {
+            if state.capture_state.dmabuf().is_some() {
+                frame.linux_dmabuf(CAPTURE_FORMAT as u32, width, height);
+            }
+            frame.buffer_done();
+        }
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        manager: &ZwlrScreencopyManagerV1,
+        _data: &(),
+    ) {
+        state.capture_state.remove_session(&manager.id());
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ZwlrScreencopyFrameV1, ScreencopyFrameData>
+    for WallyState<BackendData>
+{
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        frame: &ZwlrScreencopyFrameV1,
+        request: zwlr_screencopy_frame_v1::Request,
+        data: &ScreencopyFrameData,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        let (buffer, with_damage) = match request {
+            zwlr_screencopy_frame_v1::Request::Copy { buffer } => (buffer, false),
+            zwlr_screencopy_frame_v1::Request::CopyWithDamage { buffer } => (buffer, true),
+            zwlr_screencopy_frame_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        if data.used.swap(true, Ordering::SeqCst) {
+            frame.post_error(
+                zwlr_screencopy_frame_v1::Error::AlreadyUsed,
+                "frame was already used",
+            );
+            return;
+        }
+
+        let Some((output, region)) = data.target.clone() else {
+            frame.failed();
+            return;
+        };
+
+        if !capture::buffer_matches(&buffer, region.size) {
+            frame.post_error(
+                zwlr_screencopy_frame_v1::Error::InvalidBuffer,
+                "buffer does not match the advertised constraints",
+            );
+            return;
+        }
+
+        state.capture_state.queue(CaptureRequest {
+            output,
+            region,
+            buffer,
+            paint_cursor: data.overlay_cursor,
+            wait_for_damage: with_damage,
+            session: data.manager.clone(),
+            frame: CaptureFrame::Screencopy {
+                frame: frame.clone(),
+                with_damage,
+            },
+        });
+    }
+}
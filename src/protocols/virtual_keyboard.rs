@@ -0,0 +1,205 @@
+use std::{fs::File, io::ErrorKind, os::unix::fs::FileExt, sync::Mutex};
+
+use smithay::{
+    backend::input::{DeviceCapability, KeyState},
+    input::keyboard::SerializedMods,
+    reexports::{
+        wayland_protocols_misc::zwp_virtual_keyboard_v1::server::{
+            zwp_virtual_keyboard_manager_v1::{self, ZwpVirtualKeyboardManagerV1},
+            zwp_virtual_keyboard_v1::{self, ZwpVirtualKeyboardV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            protocol::wl_keyboard::KeymapFormat,
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+};
+
+use crate::{
+    backend::Backend,
+    state::{ClientState, WallyState},
+    virtual_input::{VirtualDevice, VirtualEvent, VirtualKey},
+};
+
+const VERSION: u32 = 1;
+/// Largest keymap accepted, generated keymaps stay well below this
+const MAX_KEYMAP_SIZE: usize = 1024 * 1024;
+
+/// State of the `zwp_virtual_keyboard_manager_v1` global
+#[derive(Debug)]
+pub struct VirtualKeyboardManagerState {
+    _global: GlobalId,
+}
+
+impl VirtualKeyboardManagerState {
+    pub fn new<BackendData: Backend + 'static>(display_handle: &DisplayHandle) -> Self {
+        let global = display_handle
+            .create_global::<WallyState<BackendData>, ZwpVirtualKeyboardManagerV1, ()>(VERSION, ());
+
+        Self { _global: global }
+    }
+}
+
+pub struct VirtualKeyboardData {
+    /// Set by the client before it sends any key
+    keymap: Mutex<Option<String>>,
+}
+
+impl<BackendData: Backend + 'static> GlobalDispatch<ZwpVirtualKeyboardManagerV1, ()>
+    for WallyState<BackendData>
+{
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwpVirtualKeyboardManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, _global_data: &()) -> bool {
+        ClientState::may_use_privileged_protocols(&client)
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ZwpVirtualKeyboardManagerV1, ()>
+    for WallyState<BackendData>
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _manager: &ZwpVirtualKeyboardManagerV1,
+        request: zwp_virtual_keyboard_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            // there is only one seat
+            zwp_virtual_keyboard_manager_v1::Request::CreateVirtualKeyboard { id, .. } => {
+                data_init.init(
+                    id,
+                    VirtualKeyboardData {
+                        keymap: Mutex::new(None),
+                    },
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn read_keymap(fd: impl Into<File>, size: u32) -> std::io::Result<String> {
+    let file = fd.into();
+
+    // the size is only the client's word, don't allocate more than the file can back
+    let len = file.metadata()?.len();
+    if size as usize > MAX_KEYMAP_SIZE || u64::from(size) > len {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("keymap size {size} exceeds the file ({len} bytes) or the limit"),
+        ));
+    }
+
+    let mut buffer = vec![0; size as usize];
+    // the client may have left the offset at the end of what it wrote
+    file.read_exact_at(&mut buffer, 0)?;
+
+    let keymap = String::from_utf8_lossy(&buffer);
+    Ok(keymap.trim_end_matches('\0').to_owned())
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ZwpVirtualKeyboardV1, VirtualKeyboardData>
+    for WallyState<BackendData>
+{
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        keyboard: &ZwpVirtualKeyboardV1,
+        request: zwp_virtual_keyboard_v1::Request,
+        data: &VirtualKeyboardData,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let zwp_virtual_keyboard_v1::Request::Keymap { format, fd, size } = request {
+            if format != KeymapFormat::XkbV1 as u32 {
+                tracing::warn!("Unsupported keymap format {format} of a virtual keyboard");
+                return;
+            }
+
+            match read_keymap(fd, size) {
+                Ok(keymap) => *data.keymap.lock().unwrap() = Some(keymap),
+                Err(err) => {
+                    tracing::warn!("Failed to read the keymap of a virtual keyboard: {err}")
+                }
+            }
+            return;
+        }
+
+        if let zwp_virtual_keyboard_v1::Request::Destroy = request {
+            return;
+        }
+
+        let Some(keymap) = data.keymap.lock().unwrap().clone() else {
+            keyboard.post_error(
+                zwp_virtual_keyboard_v1::Error::NoKeymap,
+                "no keymap was set",
+            );
+            return;
+        };
+
+        match request {
+            zwp_virtual_keyboard_v1::Request::Key {
+                time,
+                key,
+                state: key_state,
+            } => {
+                let event = VirtualEvent {
+                    device: VirtualDevice {
+                        id: keyboard.id(),
+                        capability: DeviceCapability::Keyboard,
+                    },
+                    time,
+                    inner: VirtualKey {
+                        key,
+                        state: if key_state == 0 {
+                            KeyState::Released
+                        } else {
+                            KeyState::Pressed
+                        },
+                    },
+                };
+
+                state.virtual_key(&keymap, event);
+            }
+            zwp_virtual_keyboard_v1::Request::Modifiers {
+                mods_depressed,
+                mods_latched,
+                mods_locked,
+                group,
+            } => {
+                let serialized = SerializedMods {
+                    depressed: mods_depressed,
+                    latched: mods_latched,
+                    locked: mods_locked,
+                    layout_effective: group,
+                };
+
+                state.virtual_modifiers(&keyboard.id(), &keymap, serialized);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        keyboard: &ZwpVirtualKeyboardV1,
+        _data: &VirtualKeyboardData,
+    ) {
+        state.virtual_keyboard_destroyed(&keyboard.id());
+    }
+}
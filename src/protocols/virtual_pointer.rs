@@ -0,0 +1,296 @@
+use std::sync::Mutex;
+
+use smithay::{
+    backend::input::{AxisSource, ButtonState, DeviceCapability, InputEvent},
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::virtual_pointer::v1::server::{
+            zwlr_virtual_pointer_manager_v1::{self, ZwlrVirtualPointerManagerV1},
+            zwlr_virtual_pointer_v1::{self, ZwlrVirtualPointerV1},
+        },
+        wayland_server::{
+            backend::GlobalId, protocol::wl_pointer, Client, DataInit, Dispatch, DisplayHandle,
+            GlobalDispatch, New, Resource, WEnum,
+        },
+    },
+    utils::{Logical, Point, Rectangle},
+};
+
+use crate::{
+    backend::Backend,
+    state::{ClientState, WallyState},
+    virtual_input::{
+        VirtualAxis, VirtualButton, VirtualDevice, VirtualEvent, VirtualInput, VirtualMotion,
+    },
+};
+
+const VERSION: u32 = 2;
+
+/// State of the `zwlr_virtual_pointer_manager_v1` global
+#[derive(Debug)]
+pub struct VirtualPointerManagerState {
+    _global: GlobalId,
+}
+
+impl VirtualPointerManagerState {
+    pub fn new<BackendData: Backend + 'static>(display_handle: &DisplayHandle) -> Self {
+        let global = display_handle
+            .create_global::<WallyState<BackendData>, ZwlrVirtualPointerManagerV1, ()>(VERSION, ());
+
+        Self { _global: global }
+    }
+}
+
+pub struct VirtualPointerData {
+    /// Absolute motion is mapped to this output, or to all outputs if unset
+    output: Option<Output>,
+    /// Scrolling sent since the last frame
+    axis: Mutex<VirtualAxis>,
+}
+
+impl<BackendData: Backend + 'static> GlobalDispatch<ZwlrVirtualPointerManagerV1, ()>
+    for WallyState<BackendData>
+{
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrVirtualPointerManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, _global_data: &()) -> bool {
+        ClientState::may_use_privileged_protocols(&client)
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ZwlrVirtualPointerManagerV1, ()>
+    for WallyState<BackendData>
+{
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _manager: &ZwlrVirtualPointerManagerV1,
+        request: zwlr_virtual_pointer_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        // there is only one seat
+        let (id, output) = match request {
+            zwlr_virtual_pointer_manager_v1::Request::CreateVirtualPointer { id, .. } => (id, None),
+            zwlr_virtual_pointer_manager_v1::Request::CreateVirtualPointerWithOutput {
+                id,
+                output,
+                ..
+            } => (id, output.as_ref().and_then(Output::from_resource)),
+            zwlr_virtual_pointer_manager_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        data_init.init(
+            id,
+            VirtualPointerData {
+                output,
+                axis: Mutex::new(VirtualAxis::default()),
+            },
+        );
+    }
+}
+
+fn axis_source(source: wl_pointer::AxisSource) -> AxisSource {
+    match source {
+        wl_pointer::AxisSource::Finger => AxisSource::Finger,
+        wl_pointer::AxisSource::Continuous => AxisSource::Continuous,
+        wl_pointer::AxisSource::WheelTilt => AxisSource::WheelTilt,
+        _ => AxisSource::Wheel,
+    }
+}
+
+impl<BackendData: Backend + 'static> WallyState<BackendData> {
+    /// Map absolute motion to the output of a virtual pointer, or the area covered by all outputs
+    fn virtual_pointer_position(
+        &self,
+        output: Option<&Output>,
+        position: Point<f64, Logical>,
+        extent: Point<f64, Logical>,
+    ) -> Option<Point<f64, Logical>> {
+        let area = match output {
+            Some(output) => self.space.output_geometry(output)?,
+            None => self
+                .space
+                .outputs()
+                .filter_map(|output| self.space.output_geometry(output))
+                .reduce(|area, geometry| area.merge(geometry))?,
+        };
+        let area: Rectangle<f64, Logical> = area.to_f64();
+        let x = area.loc.x + position.x / extent.x * area.size.w;
+        let y = area.loc.y + position.y / extent.y * area.size.h;
+
+        Some(Point::from((x, y)))
+    }
+}
+
+impl<BackendData: Backend + 'static> Dispatch<ZwlrVirtualPointerV1, VirtualPointerData>
+    for WallyState<BackendData>
+{
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        pointer: &ZwlrVirtualPointerV1,
+        request: zwlr_virtual_pointer_v1::Request,
+        data: &VirtualPointerData,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        let device = VirtualDevice {
+            id: pointer.id(),
+            capability: DeviceCapability::Pointer,
+        };
+
+        let axis = |axis: WEnum<wl_pointer::Axis>| match axis {
+            WEnum::Value(axis) => Some(axis),
+            WEnum::Unknown(_) => {
+                pointer.post_error(zwlr_virtual_pointer_v1::Error::InvalidAxis, "invalid axis");
+                None
+            }
+        };
+
+        match request {
+            zwlr_virtual_pointer_v1::Request::Motion { time, dx, dy } => {
+                let event = VirtualEvent {
+                    device,
+                    time,
+                    inner: VirtualMotion {
+                        delta: (dx, dy).into(),
+                    },
+                };
+                state.process_input_event(InputEvent::<VirtualInput>::PointerMotion { event });
+            }
+            zwlr_virtual_pointer_v1::Request::MotionAbsolute {
+                time,
+                x,
+                y,
+                x_extent,
+                y_extent,
+            } => {
+                if x_extent == 0 || y_extent == 0 {
+                    return;
+                }
+
+                let Some(position) = state.virtual_pointer_position(
+                    data.output.as_ref(),
+                    (x as f64, y as f64).into(),
+                    (x_extent as f64, y_extent as f64).into(),
+                ) else {
+                    return;
+                };
+
+                state.pointer_motion_absolute(position, time);
+            }
+            zwlr_virtual_pointer_v1::Request::Button {
+                time,
+                button,
+                state: button_state,
+            } => {
+                let button_state = match button_state {
+                    WEnum::Value(wl_pointer::ButtonState::Pressed) => ButtonState::Pressed,
+                    _ => ButtonState::Released,
+                };
+
+                let event = VirtualEvent {
+                    device,
+                    time,
+                    inner: VirtualButton {
+                        button,
+                        state: button_state,
+                    },
+                };
+                state.process_input_event(InputEvent::<VirtualInput>::PointerButton { event });
+            }
+            zwlr_virtual_pointer_v1::Request::Axis {
+                axis: wl_axis,
+                value,
+                ..
+            } => {
+                let Some(wl_axis) = axis(wl_axis) else {
+                    return;
+                };
+
+                let mut pending = data.axis.lock().unwrap();
+                match wl_axis {
+                    wl_pointer::Axis::HorizontalScroll => {
+                        *pending.horizontal.get_or_insert(0.0) += value
+                    }
+                    _ => *pending.vertical.get_or_insert(0.0) += value,
+                }
+            }
+            zwlr_virtual_pointer_v1::Request::AxisDiscrete {
+                axis: wl_axis,
+                value,
+                discrete,
+                ..
+            } => {
+                let Some(wl_axis) = axis(wl_axis) else {
+                    return;
+                };
+
+                let mut pending = data.axis.lock().unwrap();
+                let v120 = discrete as f64 * 120.0;
+                match wl_axis {
+                    wl_pointer::Axis::HorizontalScroll => {
+                        *pending.horizontal.get_or_insert(0.0) += value;
+                        *pending.horizontal_v120.get_or_insert(0.0) += v120;
+                    }
+                    _ => {
+                        *pending.vertical.get_or_insert(0.0) += value;
+                        *pending.vertical_v120.get_or_insert(0.0) += v120;
+                    }
+                }
+            }
+            zwlr_virtual_pointer_v1::Request::AxisStop { axis: wl_axis, .. } => {
+                let Some(wl_axis) = axis(wl_axis) else {
+                    return;
+                };
+
+                // a zero amount from a finger is a stop, see `process_input_event`
+                let mut pending = data.axis.lock().unwrap();
+                match wl_axis {
+                    wl_pointer::Axis::HorizontalScroll => pending.horizontal = Some(0.0),
+                    _ => pending.vertical = Some(0.0),
+                }
+            }
+            zwlr_virtual_pointer_v1::Request::AxisSource {
+                axis_source: source,
+            } => {
+                let WEnum::Value(source) = source else {
+                    pointer.post_error(
+                        zwlr_virtual_pointer_v1::Error::InvalidAxisSource,
+                        "invalid axis source",
+                    );
+                    return;
+                };
+
+                data.axis.lock().unwrap().source = Some(axis_source(source));
+            }
+            zwlr_virtual_pointer_v1::Request::Frame => {
+                let pending = std::mem::take(&mut *data.axis.lock().unwrap());
+                if pending.is_empty() {
+                    return;
+                }
+
+                let event = VirtualEvent {
+                    device,
+                    time: state.clock.now().as_millis(),
+                    inner: pending,
+                };
+                state.process_input_event(InputEvent::<VirtualInput>::PointerAxis { event });
+            }
+            zwlr_virtual_pointer_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
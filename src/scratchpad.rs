@@ -0,0 +1,128 @@
+use smithay::{
+    desktop::space::SpaceElement,
+    utils::{Logical, Point, Rectangle, SERIAL_COUNTER},
+};
+use tracing::warn;
+
+use crate::{backend::Backend, config::CONFIG, elements::window::WindowElement, state::WallyState};
+
+impl<BackendData: Backend> WallyState<BackendData> {
+    /// Show the named scratchpad if it is hidden and hide it if it is shown,
+    /// spawning its command if the scratchpad window does not exist yet
+    pub fn toggle_scratchpad(&mut self, name: &str) {
+        let Some(window) = self.scratchpads.get(name).cloned() else {
+            self.spawn_scratchpad(name);
+            return;
+        };
+
+        if self.space.element_location(&window).is_some() {
+            self.hide_scratchpad(&window);
+        } else {
+            self.show_scratchpad(&window);
+        }
+    }
+
+    /// Spawn the command of a scratchpad, unless it was spawned before and is still
+    /// running, in which case its window just hasn't appeared yet
+    fn spawn_scratchpad(&mut self, name: &str) {
+        let Some(command) = CONFIG.scratchpads.get(name) else {
+            warn!(name, "No command configured for scratchpad");
+            return;
+        };
+
+        if let Some(child) = self.pending_scratchpads.get_mut(name) {
+            if matches!(child.try_wait(), Ok(None)) {
+                return;
+            }
+        }
+
+        match self.spawn_shell(command) {
+            Some(child) => {
+                self.pending_scratchpads.insert(name.to_owned(), child);
+            }
+            None => {
+                self.pending_scratchpads.remove(name);
+            }
+        }
+    }
+
+    pub fn is_scratchpad(&self, window: &WindowElement) -> bool {
+        self.scratchpads
+            .values()
+            .any(|scratchpad| scratchpad == window)
+    }
+
+    /// Map a scratchpad window centered on the active monitor and focus it
+    pub fn show_scratchpad(&mut self, window: &WindowElement) {
+        let Some(output_geometry) = self.active_monitor_index().and_then(|index| {
+            self.space
+                .output_geometry(self.monitors[index].output_ref())
+        }) else {
+            return;
+        };
+
+        self.space
+            .map_element(window.clone(), output_geometry.loc, true);
+        self.center_scratchpad(window, output_geometry);
+
+        let keyboard = self.seat.get_keyboard().unwrap();
+        keyboard.set_focus(
+            self,
+            window.wl_surface().map(|surface| surface.into_owned()),
+            SERIAL_COUNTER.next_serial(),
+        );
+    }
+
+    /// Unmap a scratchpad window from the space while keeping it alive
+    pub fn hide_scratchpad(&mut self, window: &WindowElement) {
+        self.space.unmap_elem(window);
+
+        let keyboard = self.seat.get_keyboard().unwrap();
+        if keyboard
+            .current_focus()
+            .is_some_and(|focus| window.surface_matches(&focus))
+        {
+            keyboard.set_focus(self, None, SERIAL_COUNTER.next_serial());
+        }
+    }
+
+    /// Keep a shown scratchpad window centered on its output as its size changes
+    pub fn refresh_scratchpad(&mut self, window: &WindowElement) {
+        if self.space.element_location(window).is_none() {
+            return;
+        }
+
+        let output_geometry = self
+            .space
+            .outputs_for_element(window)
+            .first()
+            .and_then(|output| self.space.output_geometry(output))
+            .or_else(|| {
+                self.active_monitor_index().and_then(|index| {
+                    self.space
+                        .output_geometry(self.monitors[index].output_ref())
+                })
+            });
+
+        if let Some(output_geometry) = output_geometry {
+            self.center_scratchpad(window, output_geometry);
+        }
+    }
+
+    fn center_scratchpad(
+        &mut self,
+        window: &WindowElement,
+        output_geometry: Rectangle<i32, Logical>,
+    ) {
+        let size = SpaceElement::geometry(window).size;
+        let location: Point<i32, Logical> = (
+            output_geometry.loc.x + (output_geometry.size.w - size.w) / 2,
+            output_geometry.loc.y + (output_geometry.size.h - size.h) / 2,
+        )
+            .into();
+
+        if self.space.element_location(window) != Some(location) {
+            self.space.map_element(window.clone(), location, false);
+        }
+    }
+}
@@ -1,36 +1,75 @@
 use std::{
-    process::Command,
-    sync::{atomic::AtomicBool, Arc, Mutex},
+    collections::{HashMap, HashSet},
+    process::{Child, Command},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
 };
 
 use smithay::{
-    desktop::{PopupManager, Space, WindowSurfaceType},
+    backend::renderer::element::memory::MemoryRenderBuffer,
+    desktop::{space::SpaceElement, PopupManager, Space, WindowSurfaceType},
     input::{
-        pointer::{CursorImageAttributes, CursorImageStatus, PointerHandle},
-        Seat, SeatState,
+        keyboard::{LedState, ModifiersState},
+        pointer::{CursorImageAttributes, CursorImageStatus, MotionEvent, PointerHandle},
+        Seat, SeatHandler, SeatState,
     },
+    output::Output,
     reexports::{
         calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction},
+        input,
         wayland_server::{
             backend::{ClientData, ClientId, DisconnectReason},
             protocol::wl_surface::WlSurface,
-            Display, DisplayHandle,
+            Client, Display, DisplayHandle,
         },
     },
-    utils::{Clock, IsAlive, Logical, Monotonic, Physical, Point, Scale},
+    utils::{Clock, IsAlive, Logical, Monotonic, Physical, Point, Scale, SERIAL_COUNTER},
     wayland::{
         compositor::{self, CompositorClientState, CompositorState},
+        cursor_shape::CursorShapeManagerState,
+        idle_inhibit::IdleInhibitManagerState,
+        idle_notify::IdleNotifierState,
+        input_method::InputMethodManagerState,
+        keyboard_shortcuts_inhibit::KeyboardShortcutsInhibitState,
         output::OutputManagerState,
-        selection::data_device::DataDeviceState,
+        pointer_constraints::{with_pointer_constraint, PointerConstraintsState},
+        pointer_gestures::PointerGesturesState,
+        relative_pointer::RelativePointerManagerState,
+        selection::{
+            data_device::DataDeviceState, primary_selection::PrimarySelectionState,
+            wlr_data_control::DataControlState,
+        },
+        session_lock::SessionLockManagerState,
         shell::xdg::{decoration::XdgDecorationState, XdgShellState},
         shm::ShmState,
         socket::ListeningSocketSource,
+        tablet_manager::TabletManagerState,
+        text_input::TextInputManagerState,
     },
 };
 
 use crate::{
-    backend::Backend, elements::window::WindowElement, focus::PointerFocusTarget, monitor::Monitor,
-    types::keybind::Action,
+    backend::Backend,
+    capture::CaptureState,
+    clipboard::ClipboardKeeper,
+    config::CONFIG,
+    cursor::CursorTheme,
+    elements::window::WindowElement,
+    focus::PointerFocusTarget,
+    gesture::{Swipe, WorkspaceSwipe},
+    ipc::{self, IpcMessage, IpcState},
+    keybind::KeybindState,
+    lock::LockState,
+    monitor::Monitor,
+    protocols::{
+        image_copy_capture::ImageCopyCaptureState, output_management::OutputManagementState,
+        screencopy::ScreencopyManagerState, virtual_keyboard::VirtualKeyboardManagerState,
+        virtual_pointer::VirtualPointerManagerState,
+    },
+    types::{keybind::Action, window_rule::WindowRule},
+    virtual_input::VirtualKeymapState,
 };
 
 #[derive(Debug)]
@@ -40,21 +79,73 @@ pub struct WallyState<BackendData: Backend + 'static> {
     pub clock: Clock<Monotonic>,
     pub start_time: std::time::Instant,
     pub socket_name: String,
+    /// `None` if the IPC socket could not be created
+    pub ipc: Option<IpcState>,
     pub display_handle: DisplayHandle,
+    pub loop_handle: LoopHandle<'static, WallyState<BackendData>>,
 
     pub monitors: Vec<Monitor>,
+    /// Outputs disabled through output management, they have no monitor until enabled again
+    pub disabled_outputs: Vec<Output>,
     pub space: Space<WindowElement>,
+    /// Scratchpad windows by name, hidden scratchpads are unmapped from the space
+    pub scratchpads: HashMap<String, WindowElement>,
+    /// Processes spawned for scratchpads whose window has not appeared yet
+    pub pending_scratchpads: HashMap<String, Child>,
 
     // Smithay State
     pub cursor_status: CursorImageStatus,
+    /// Cursor of the tablet tool in proximity, drawn instead of `cursor_status`
+    pub tablet_cursor_status: Option<CursorImageStatus>,
+    pub cursor_theme: CursorTheme,
     pub compositor_state: CompositorState,
+    pub cursor_shape_manager_state: CursorShapeManagerState,
+    pub tablet_manager_state: TabletManagerState,
     pub xdg_shell_state: XdgShellState,
     pub xdg_decoration_state: XdgDecorationState,
     pub shm_state: ShmState,
     pub output_manager_state: OutputManagerState,
+    pub output_management_state: OutputManagementState,
     pub seat_state: SeatState<WallyState<BackendData>>,
     pub data_device_state: DataDeviceState,
+    pub primary_selection_state: PrimarySelectionState,
+    pub data_control_state: DataControlState,
+    pub clipboard_keeper: ClipboardKeeper,
     pub popups: PopupManager,
+    pub screencopy_manager_state: ScreencopyManagerState,
+    pub image_copy_capture_state: ImageCopyCaptureState,
+    pub capture_state: CaptureState,
+    pub session_lock_manager_state: SessionLockManagerState,
+    pub lock_state: LockState,
+    pub idle_notifier_state: IdleNotifierState<WallyState<BackendData>>,
+    pub idle_inhibit_manager_state: IdleInhibitManagerState,
+    /// Surfaces with an active idle inhibitor, they only inhibit idle while visible
+    pub idle_inhibitors: HashSet<WlSurface>,
+
+    pub pointer_constraints_state: PointerConstraintsState,
+    /// Offered on every backend, virtual pointers report relative motion everywhere
+    pub relative_pointer_manager_state: RelativePointerManagerState,
+    /// Surface-local position of the pointer once the lock of the surface is released
+    pub cursor_position_hint: Option<(WlSurface, Point<f64, Logical>)>,
+
+    pub pointer_gestures_state: PointerGesturesState,
+    /// Swipe currently intercepted as a compositor gesture
+    pub swipe: Option<Swipe>,
+    pub workspace_swipe: Option<WorkspaceSwipe>,
+
+    pub keybind_state: KeybindState,
+    pub keyboard_shortcuts_inhibit_state: KeyboardShortcutsInhibitState,
+    pub input_method_manager_state: InputMethodManagerState,
+    pub text_input_manager_state: TextInputManagerState,
+    pub virtual_keyboard_manager_state: VirtualKeyboardManagerState,
+    pub virtual_pointer_manager_state: VirtualPointerManagerState,
+    pub virtual_keymap_state: VirtualKeymapState,
+    /// Caps, num and scroll lock as last reported by the keyboard
+    pub led_state: LedState,
+    /// Index of the active keyboard layout as last reported to IPC subscribers
+    pub keyboard_layout_index: u32,
+    /// Keyboards of libinput based backends, their LEDs follow `led_state`
+    pub libinput_keyboards: Vec<input::Device>,
 
     pub seat: Seat<WallyState<BackendData>>,
     pub pointer: PointerHandle<WallyState<BackendData>>,
@@ -71,56 +162,153 @@ impl<BackendData: Backend> WallyState<BackendData> {
         let display_handle = display.handle();
 
         let compositor_state = CompositorState::new::<Self>(&display_handle);
+        let cursor_shape_manager_state = CursorShapeManagerState::new::<Self>(&display_handle);
+        let tablet_manager_state = TabletManagerState::new::<Self>(&display_handle);
         let xdg_shell_state = XdgShellState::new::<Self>(&display_handle);
         let xdg_decoration_state = XdgDecorationState::new::<Self>(&display_handle);
         let shm_state = ShmState::new::<Self>(&display_handle, vec![]);
         let output_manager_state = OutputManagerState::new_with_xdg_output::<Self>(&display_handle);
+        let output_management_state = OutputManagementState::new::<BackendData>(&display_handle);
         let mut seat_state = SeatState::new();
         let data_device_state = DataDeviceState::new::<Self>(&display_handle);
+        let primary_selection_state = PrimarySelectionState::new::<Self>(&display_handle);
+        // clipboard managers get access to both the clipboard and the primary selection
+        let data_control_state = DataControlState::new::<Self, _>(
+            &display_handle,
+            Some(&primary_selection_state),
+            ClientState::may_use_privileged_protocols,
+        );
         let popups = PopupManager::default();
+        let screencopy_manager_state = ScreencopyManagerState::new::<BackendData>(&display_handle);
+        let image_copy_capture_state = ImageCopyCaptureState::new::<BackendData>(&display_handle);
+        let session_lock_manager_state =
+            SessionLockManagerState::new::<Self, _>(&display_handle, |_| true);
+        let idle_notifier_state = IdleNotifierState::new(&display_handle, handle.clone());
+        let idle_inhibit_manager_state = IdleInhibitManagerState::new::<Self>(&display_handle);
+        let clipboard_keeper = ClipboardKeeper::new(&handle);
+        let pointer_constraints_state = PointerConstraintsState::new::<Self>(&display_handle);
+        // virtual pointers deliver relative motion on every backend
+        let relative_pointer_manager_state =
+            RelativePointerManagerState::new::<Self>(&display_handle);
+        let keyboard_shortcuts_inhibit_state =
+            KeyboardShortcutsInhibitState::new::<Self>(&display_handle);
+        // input methods are trusted as much as the keyboard they sit in front of
+        let input_method_manager_state =
+            InputMethodManagerState::new::<Self, _>(&display_handle, |_| true);
+        let text_input_manager_state = TextInputManagerState::new::<Self>(&display_handle);
+        let virtual_keyboard_manager_state =
+            VirtualKeyboardManagerState::new::<BackendData>(&display_handle);
+        let virtual_pointer_manager_state =
+            VirtualPointerManagerState::new::<BackendData>(&display_handle);
+        let pointer_gestures_state = PointerGesturesState::new::<Self>(&display_handle);
 
         let seat_name = backend_data.seat_name();
         let mut seat: Seat<Self> = seat_state.new_wl_seat(&display_handle, seat_name);
 
         // Notify clients that we have a keyboard, for the sake of the example we assume that keyboard is always present.
         // You may want to track keyboard hot-plug in real compositor.
-        seat.add_keyboard(Default::default(), 200, 25).unwrap();
+        if let Err(err) = seat.add_keyboard(
+            CONFIG.xkb.xkb_config(),
+            CONFIG.repeat_delay,
+            CONFIG.repeat_rate,
+        ) {
+            tracing::warn!("Invalid xkb config, falling back to the default keymap: {err}");
+            seat.add_keyboard(Default::default(), CONFIG.repeat_delay, CONFIG.repeat_rate)
+                .unwrap();
+        }
 
         // Notify clients that we have a pointer (mouse)
         // Here we assume that there is always pointer plugged in
         let pointer = seat.add_pointer();
 
+        // touch points are focused per slot, see `TouchDragGrab`
+        seat.add_touch();
+
         // A space represents a two-dimensional plane. Windows and Outputs can be mapped onto it.
         //
         // Windows get a position and stacking order through mapping.
         // Outputs become views of a part of the Space and can be rendered via Space::render_output.
         let space = Space::default();
 
-        let socket_name = Self::init_wayland_listener(display, handle);
+        let socket_name = Self::init_wayland_listener(display, handle.clone());
+        let ipc = IpcState::new(&handle, &socket_name);
 
-        Self {
+        let mut state = Self {
             running: AtomicBool::new(true),
             backend_data,
             clock: Clock::new(),
             start_time,
             display_handle,
+            loop_handle: handle,
 
             monitors: Vec::new(),
+            disabled_outputs: Vec::new(),
             space,
+            scratchpads: HashMap::new(),
+            pending_scratchpads: HashMap::new(),
             socket_name,
+            ipc,
 
             cursor_status: CursorImageStatus::default_named(),
+            tablet_cursor_status: None,
+            cursor_theme: CursorTheme::load(),
             compositor_state,
+            cursor_shape_manager_state,
+            tablet_manager_state,
             xdg_shell_state,
             xdg_decoration_state,
             shm_state,
             output_manager_state,
+            output_management_state,
             seat_state,
             data_device_state,
+            primary_selection_state,
+            data_control_state,
+            clipboard_keeper,
             popups,
+            screencopy_manager_state,
+            image_copy_capture_state,
+            capture_state: CaptureState::default(),
+            session_lock_manager_state,
+            lock_state: LockState::default(),
+            idle_notifier_state,
+            idle_inhibit_manager_state,
+            idle_inhibitors: HashSet::new(),
+            pointer_constraints_state,
+            relative_pointer_manager_state,
+            cursor_position_hint: None,
+            pointer_gestures_state,
+            swipe: None,
+            workspace_swipe: None,
+            keybind_state: KeybindState::default(),
+            keyboard_shortcuts_inhibit_state,
+            input_method_manager_state,
+            text_input_manager_state,
+            virtual_keyboard_manager_state,
+            virtual_pointer_manager_state,
+            virtual_keymap_state: VirtualKeymapState::default(),
+            led_state: LedState::default(),
+            keyboard_layout_index: 0,
+            libinput_keyboards: Vec::new(),
             seat,
             pointer,
+        };
+
+        if CONFIG.numlock_on_start {
+            let keyboard = state.seat.get_keyboard().unwrap();
+            keyboard.set_modifier_state(ModifiersState {
+                num_lock: true,
+                ..keyboard.modifier_state()
+            });
+
+            // setting the modifiers directly bypasses the lock state tracking of key presses
+            let mut led_state = state.led_state;
+            led_state.num = Some(true);
+            let seat = state.seat.clone();
+            state.led_state_changed(&seat, led_state);
         }
+
+        state
     }
 
     fn init_wayland_listener(
@@ -142,10 +330,11 @@ impl<BackendData: Backend> WallyState<BackendData> {
                 // Inside the callback, you should insert the client into the display.
                 //
                 // You may also associate some data with the client when inserting the client.
-                state
+                let client = state
                     .display_handle
                     .insert_client(client_stream, Arc::new(ClientState::new()))
                     .unwrap();
+                ClientState::check_trusted(&client, &state.display_handle);
             })
             .expect("Failed to init the wayland event source.");
 
@@ -166,58 +355,298 @@ impl<BackendData: Backend> WallyState<BackendData> {
         socket_name
     }
 
-    pub fn get_cursor_data(&mut self, scale: Scale<f64>) -> (bool, Point<i32, Physical>) {
-        if let CursorImageStatus::Surface(ref surface) = self.cursor_status {
-            if !surface.alive() {
-                self.cursor_status = CursorImageStatus::default_named();
+    pub fn get_cursor_data(
+        &mut self,
+        scale: Scale<f64>,
+    ) -> (bool, Point<i32, Physical>, Option<MemoryRenderBuffer>) {
+        for status in std::iter::once(&mut self.cursor_status).chain(&mut self.tablet_cursor_status)
+        {
+            if let CursorImageStatus::Surface(ref surface) = status {
+                if !surface.alive() {
+                    *status = CursorImageStatus::default_named();
+                }
             }
         }
 
+        let cursor_status = self.current_cursor_status().clone();
         let cursor_pos = self.pointer.current_location();
-        let cursor_location = if let CursorImageStatus::Surface(ref surface) = self.cursor_status {
-            // the 'hotspot' is the part of the cursor image where the tip of the arrow
-            // is situated.
-            let hotspot = compositor::with_states(surface, |states| {
-                states
-                    .data_map
-                    .get::<Mutex<CursorImageAttributes>>()
-                    .unwrap()
-                    .lock()
-                    .unwrap()
-                    .hotspot
-            });
-            cursor_pos - hotspot.to_f64()
-        } else {
-            cursor_pos
+        let (cursor_location, cursor_buffer) = match cursor_status {
+            CursorImageStatus::Surface(ref surface) => {
+                // the 'hotspot' is the part of the cursor image where the tip of the arrow
+                // is situated.
+                let hotspot = compositor::with_states(surface, |states| {
+                    states
+                        .data_map
+                        .get::<Mutex<CursorImageAttributes>>()
+                        .unwrap()
+                        .lock()
+                        .unwrap()
+                        .hotspot
+                });
+                (cursor_pos - hotspot.to_f64(), None)
+            }
+            CursorImageStatus::Named(icon) => {
+                let time = self.start_time.elapsed();
+                match self.cursor_theme.image(icon, scale.x, time) {
+                    Some((buffer, hotspot)) => (cursor_pos - hotspot, Some(buffer)),
+                    None => (cursor_pos, None),
+                }
+            }
+            CursorImageStatus::Hidden => (cursor_pos, None),
         };
 
         let cursor_location = cursor_location.to_physical(scale).to_i32_round();
 
-        let cursor_visible = !matches!(self.cursor_status, CursorImageStatus::Surface(_));
+        // only fall back to the host cursor if the cursor theme is missing the named cursor
+        let cursor_visible =
+            matches!(cursor_status, CursorImageStatus::Named(_)) && cursor_buffer.is_none();
 
-        (cursor_visible, cursor_location)
+        (cursor_visible, cursor_location, cursor_buffer)
+    }
+
+    /// The cursor drawn at the pointer location, a tablet tool in proximity takes precedence
+    pub fn current_cursor_status(&self) -> &CursorImageStatus {
+        self.tablet_cursor_status
+            .as_ref()
+            .unwrap_or(&self.cursor_status)
     }
 
     pub fn add_monitor(&mut self, monitor: Monitor) {
         self.monitors.push(monitor);
+        self.output_configuration_changed();
+    }
+
+    /// Index of the monitor the pointer currently is on, falling back to the first monitor
+    pub fn active_monitor_index(&self) -> Option<usize> {
+        let pointer_location = self.pointer.current_location();
+
+        self.monitors
+            .iter()
+            .position(|monitor| {
+                self.space
+                    .output_geometry(monitor.output_ref())
+                    .is_some_and(|geometry| geometry.to_f64().contains(pointer_location))
+            })
+            .or((!self.monitors.is_empty()).then_some(0))
+    }
+
+    pub fn window_for_surface(&self, surface: &WlSurface) -> Option<WindowElement> {
+        self.monitors
+            .iter()
+            .flat_map(|monitor| monitor.windows())
+            .chain(self.scratchpads.values())
+            .find(|window| window.surface_matches(surface))
+            .cloned()
+    }
+
+    /// Raise a window to the top of the stack, below any floating windows unless it floats itself
+    pub fn raise_window(&mut self, window: &WindowElement, activate: bool) {
+        self.space.raise_element(window, activate);
+        self.raise_floating_windows();
+    }
+
+    /// Keep floating windows stacked above all other windows
+    pub fn raise_floating_windows(&mut self) {
+        let floating: Vec<WindowElement> = self
+            .space
+            .elements()
+            .filter(|window| window.is_floating())
+            .cloned()
+            .collect();
+
+        for window in floating {
+            self.space.raise_element(&window, false);
+        }
+    }
+
+    pub fn switch_workspace(&mut self, index: usize) {
+        // the active workspace might still be changed by a swipe
+        self.finish_workspace_swipe();
+
+        let Some(monitor_index) = self.active_monitor_index() else {
+            return;
+        };
+
+        self.monitors[monitor_index].switch_workspace(index, &mut self.space);
+
+        // the focused window might have been hidden along with the previous workspace
+        let keyboard = self.seat.get_keyboard().unwrap();
+        keyboard.set_focus(self, None, SERIAL_COUNTER.next_serial());
+    }
+
+    fn cycle_workspace(&mut self, offset: isize) {
+        self.finish_workspace_swipe();
+
+        let Some(monitor_index) = self.active_monitor_index() else {
+            return;
+        };
+
+        let monitor = &self.monitors[monitor_index];
+        let count = monitor.workspace_count() as isize;
+        let index = (monitor.active_workspace_index() as isize + offset).rem_euclid(count);
+
+        self.switch_workspace(index as usize);
     }
 
     pub fn handle_action(&mut self, action: Action) {
         match action {
+            Action::Quit => self.running.store(false, Ordering::SeqCst),
+            Action::NextWorkspace => self.cycle_workspace(1),
+            Action::PrevWorkspace => self.cycle_workspace(-1),
             Action::Spawn(command) => {
-                Command::new(command)
-                    .env("WAYLAND_DISPLAY", &self.socket_name) // FIXME: xwayland DISPLAY
-                    .spawn()
-                    .ok();
+                self.spawn(&command);
             }
+            Action::ToggleScratchpad(name) => self.toggle_scratchpad(&name),
+            Action::SwitchLayout => self.switch_layout(),
+            Action::RemoveWindow => self.close_focused_window(),
+            Action::EnterMode(name) => self.set_binding_mode(Some(name)),
+            Action::ExitMode => self.set_binding_mode(None),
             _ => {}
         }
     }
 
+    fn close_focused_window(&mut self) {
+        let keyboard = self.seat.get_keyboard().unwrap();
+        let Some(focus) = keyboard.current_focus() else {
+            return;
+        };
+
+        if let Some(window) = self
+            .space
+            .elements()
+            .find(|window| window.surface_matches(&focus))
+        {
+            window.close();
+        }
+    }
+
+    fn switch_layout(&mut self) {
+        let keyboard = self.seat.get_keyboard().unwrap();
+        let layout = keyboard.with_xkb_state(self, |mut context| {
+            context.cycle_next_layout();
+            context.xkb().lock().unwrap().active_layout().0
+        });
+
+        self.keyboard_layout_changed(layout);
+    }
+
+    /// Let IPC subscribers know the active layout, unless it already is the one last reported
+    pub fn keyboard_layout_changed(&mut self, layout: u32) {
+        if layout == self.keyboard_layout_index {
+            return;
+        }
+        self.keyboard_layout_index = layout;
+
+        let (index, name) = self.keyboard_layout();
+        self.ipc_broadcast(IpcMessage::KeyboardLayout { index, name });
+    }
+
+    /// Index and name of the active keyboard layout
+    pub fn keyboard_layout(&mut self) -> (u32, String) {
+        let keyboard = self.seat.get_keyboard().unwrap();
+        keyboard.with_xkb_state(self, |context| {
+            let xkb = context.xkb().lock().unwrap();
+            let layout = xkb.active_layout();
+            (layout.0, xkb.layout_name(layout).to_owned())
+        })
+    }
+
+    pub fn spawn(&self, command: &str) -> Option<Child> {
+        self.spawn_command(Command::new(command))
+    }
+
+    /// Spawn a command line interpreted by `sh`, for commands that come with arguments
+    pub fn spawn_shell(&self, command: &str) -> Option<Child> {
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(command);
+        self.spawn_command(shell)
+    }
+
+    fn spawn_command(&self, mut command: Command) -> Option<Child> {
+        command
+            .env("WAYLAND_DISPLAY", &self.socket_name) // FIXME: xwayland DISPLAY
+            .envs(
+                self.ipc
+                    .as_ref()
+                    .map(|ipc| (ipc::SOCKET_ENV, ipc.socket_path())),
+            )
+            .env("XCURSOR_THEME", self.cursor_theme.name())
+            .env("XCURSOR_SIZE", self.cursor_theme.size().to_string())
+            .spawn()
+            .ok()
+    }
+
+    /// Called whenever the app_id or title of a window changes. The border and opacity rules
+    /// follow the change, placement rules only apply when the window is first mapped
+    pub fn window_metadata_changed(&mut self, window: &WindowElement) {
+        let app_id = window.app_id();
+        let title = window.title();
+        tracing::debug!(?app_id, ?title, "Window metadata changed");
+
+        // the initial rules are applied along with the first configure
+        if window.initial_configure_sent() {
+            let rule =
+                WindowRule::resolve(&CONFIG.window_rules, app_id.as_deref(), title.as_deref());
+            window.apply_appearance_rule(&rule);
+        }
+
+        self.ipc_broadcast(IpcMessage::WindowChanged { app_id, title });
+    }
+
+    /// Re-evaluate what is under the pointer without it moving,
+    /// for when whatever was under it disappeared or got covered
+    pub fn refresh_pointer_focus(&mut self) {
+        let pointer = self.pointer.clone();
+        let location = pointer.current_location();
+        let under = self.surface_under(location);
+
+        pointer.motion(
+            self,
+            under,
+            &MotionEvent {
+                location,
+                serial: SERIAL_COUNTER.next_serial(),
+                time: self.clock.now().as_millis(),
+            },
+        );
+        pointer.frame(self);
+    }
+
+    /// Warp the pointer to the position hinted by a client, once its pointer lock was released
+    pub fn apply_cursor_position_hint(&mut self) {
+        let Some((surface, location)) = self.cursor_position_hint.clone() else {
+            return;
+        };
+
+        let locked = with_pointer_constraint(&surface, &self.pointer, |constraint| {
+            constraint.is_some_and(|constraint| constraint.is_active())
+        });
+        if locked {
+            return;
+        }
+
+        self.cursor_position_hint = None;
+
+        // windows are focused as a whole, their surface is drawn at the element's render location
+        let origin = self.window_for_surface(&surface).and_then(|window| {
+            self.space
+                .element_location(&window)
+                .map(|location| location - window.geometry().loc)
+        });
+
+        if let Some(origin) = origin {
+            self.pointer.set_location(origin.to_f64() + location);
+        }
+    }
+
     pub fn surface_under(
         &self,
         pos: Point<f64, Logical>,
     ) -> Option<(PointerFocusTarget, Point<f64, Logical>)> {
+        if self.is_locked() {
+            return self.lock_surface_under(pos);
+        }
+
         self.space
             .element_under(pos)
             .and_then(|(window, location)| {
@@ -231,14 +660,44 @@ impl<BackendData: Backend> WallyState<BackendData> {
 #[derive(Debug)]
 pub struct ClientState {
     pub compositor_state: CompositorClientState,
+    /// Whether the client's executable is one of `trusted_clients`
+    pub trusted: AtomicBool,
 }
 
 impl ClientState {
     pub fn new() -> Self {
         Self {
             compositor_state: CompositorClientState::default(),
+            trusted: AtomicBool::new(false),
         }
     }
+
+    /// Check the executable of a newly connected client against `trusted_clients`.
+    /// The full path is compared, so a binary of the same name elsewhere isn't trusted
+    fn check_trusted(client: &Client, display_handle: &DisplayHandle) {
+        let Ok(credentials) = client.get_credentials(display_handle) else {
+            return;
+        };
+
+        let trusted = std::fs::read_link(format!("/proc/{}/exe", credentials.pid))
+            .is_ok_and(|exe| CONFIG.trusted_clients.contains(&exe));
+
+        if let Some(data) = client.get_data::<ClientState>() {
+            data.trusted.store(trusted, Ordering::SeqCst);
+        }
+    }
+
+    pub fn is_trusted(&self) -> bool {
+        self.trusted.load(Ordering::SeqCst)
+    }
+
+    /// Whether a client may use privileged protocols, like virtual input and data control
+    pub fn may_use_privileged_protocols(client: &Client) -> bool {
+        !CONFIG.privileged_protocols_trusted_only
+            || client
+                .get_data::<ClientState>()
+                .is_some_and(ClientState::is_trusted)
+    }
 }
 
 impl ClientData for ClientState {
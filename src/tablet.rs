@@ -0,0 +1,181 @@
+use smithay::{
+    backend::input::{
+        Device, DeviceCapability, Event, InputBackend, ProximityState, TabletToolButtonEvent,
+        TabletToolEvent, TabletToolProximityEvent, TabletToolTipEvent, TabletToolTipState,
+    },
+    input::pointer::{CursorImageStatus, MotionEvent},
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Point, SERIAL_COUNTER},
+    wayland::{
+        seat::WaylandFocus,
+        tablet_manager::{TabletDescriptor, TabletSeatTrait},
+    },
+};
+
+use crate::{backend::Backend, config::CONFIG, state::WallyState};
+
+impl<BackendData: Backend> WallyState<BackendData> {
+    pub fn on_device_added<D: Device>(&mut self, device: &D) {
+        if device.has_capability(DeviceCapability::TabletTool) {
+            self.seat
+                .tablet_seat()
+                .add_tablet::<Self>(&self.display_handle, &TabletDescriptor::from(device));
+        }
+    }
+
+    pub fn on_device_removed<D: Device>(&mut self, device: &D) {
+        if device.has_capability(DeviceCapability::TabletTool) {
+            let tablet_seat = self.seat.tablet_seat();
+            tablet_seat.remove_tablet(&TabletDescriptor::from(device));
+
+            if tablet_seat.count_tablets() == 0 {
+                tablet_seat.clear_tools();
+            }
+        }
+    }
+
+    /// Move the pointer along with a tablet tool, the tool's cursor is drawn in place of the pointer's
+    fn tablet_tool_motion<I: InputBackend>(
+        &mut self,
+        event: &impl TabletToolEvent<I>,
+    ) -> Option<(
+        Point<f64, Logical>,
+        Option<(WlSurface, Point<f64, Logical>)>,
+    )> {
+        let output = self.mapped_output(&event.device(), &CONFIG.tablet_outputs)?;
+        let output_geometry = self.space.output_geometry(&output)?;
+        let location =
+            event.position_transformed(output_geometry.size) + output_geometry.loc.to_f64();
+
+        let pointer = self.pointer.clone();
+        let under = self.surface_under(location);
+
+        pointer.motion(
+            self,
+            under.clone(),
+            &MotionEvent {
+                location,
+                serial: SERIAL_COUNTER.next_serial(),
+                time: event.time_msec(),
+            },
+        );
+        pointer.frame(self);
+
+        let focus = under.and_then(|(target, location)| {
+            target
+                .wl_surface()
+                .map(|surface| (surface.into_owned(), location))
+        });
+
+        Some((location, focus))
+    }
+
+    pub fn on_tablet_tool_axis<I: InputBackend>(&mut self, event: I::TabletToolAxisEvent) {
+        let Some((location, focus)) = self.tablet_tool_motion(&event) else {
+            return;
+        };
+
+        let tablet_seat = self.seat.tablet_seat();
+        let tablet = tablet_seat.get_tablet(&TabletDescriptor::from(&event.device()));
+        let tool = tablet_seat.get_tool(&event.tool());
+
+        let (Some(tablet), Some(tool)) = (tablet, tool) else {
+            return;
+        };
+
+        if event.pressure_has_changed() {
+            tool.pressure(event.pressure());
+        }
+        if event.distance_has_changed() {
+            tool.distance(event.distance());
+        }
+        if event.tilt_has_changed() {
+            tool.tilt(event.tilt());
+        }
+        if event.slider_has_changed() {
+            tool.slider_position(event.slider_position());
+        }
+        if event.rotation_has_changed() {
+            tool.rotation(event.rotation());
+        }
+        if event.wheel_has_changed() {
+            tool.wheel(event.wheel_delta(), event.wheel_delta_discrete());
+        }
+
+        tool.motion(
+            location,
+            focus,
+            &tablet,
+            SERIAL_COUNTER.next_serial(),
+            event.time_msec(),
+        );
+    }
+
+    pub fn on_tablet_tool_proximity<I: InputBackend>(
+        &mut self,
+        event: I::TabletToolProximityEvent,
+    ) {
+        let display_handle = self.display_handle.clone();
+        let tablet_seat = self.seat.tablet_seat();
+        tablet_seat.add_tool::<Self>(self, &display_handle, &event.tool());
+
+        let motion = self.tablet_tool_motion(&event);
+
+        let tablet = tablet_seat.get_tablet(&TabletDescriptor::from(&event.device()));
+        let Some(tool) = tablet_seat.get_tool(&event.tool()) else {
+            return;
+        };
+
+        match event.state() {
+            ProximityState::In => {
+                self.tablet_cursor_status = Some(CursorImageStatus::default_named());
+
+                let (Some((location, Some(focus))), Some(tablet)) = (motion, tablet) else {
+                    return;
+                };
+                tool.proximity_in(
+                    location,
+                    focus,
+                    &tablet,
+                    SERIAL_COUNTER.next_serial(),
+                    event.time_msec(),
+                );
+            }
+            ProximityState::Out => {
+                // the tool leaves the surface it entered, even when it is over no surface now
+                tool.proximity_out(event.time_msec());
+                self.tablet_cursor_status = None;
+            }
+        }
+    }
+
+    pub fn on_tablet_tool_tip<I: InputBackend>(&mut self, event: I::TabletToolTipEvent) {
+        let Some(tool) = self.seat.tablet_seat().get_tool(&event.tool()) else {
+            return;
+        };
+
+        match event.tip_state() {
+            TabletToolTipState::Down => {
+                let serial = SERIAL_COUNTER.next_serial();
+                tool.tip_down(serial, event.time_msec());
+
+                // touching a window with the pen focuses it, just like clicking it
+                self.focus_window_under(self.pointer.current_location(), serial);
+            }
+            TabletToolTipState::Up => tool.tip_up(event.time_msec()),
+        }
+    }
+
+    pub fn on_tablet_tool_button<I: InputBackend>(&mut self, event: I::TabletToolButtonEvent) {
+        let Some(tool) = self.seat.tablet_seat().get_tool(&event.tool()) else {
+            return;
+        };
+
+        tool.button(
+            event.button(),
+            event.button_state(),
+            SERIAL_COUNTER.next_serial(),
+            event.time_msec(),
+        );
+    }
+}
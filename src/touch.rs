@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use smithay::{
+    backend::input::{AbsolutePositionEvent, Device, Event, InputBackend, TouchEvent, TouchSlot},
+    input::{
+        touch::{
+            DownEvent, GrabStartData, MotionEvent, OrientationEvent, ShapeEvent, TouchGrab,
+            TouchInnerHandle, UpEvent,
+        },
+        SeatHandler,
+    },
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{IsAlive, Logical, Point, Serial, SERIAL_COUNTER},
+};
+
+use crate::{backend::Backend, config::CONFIG, state::WallyState};
+
+type TouchFocus = (WlSurface, Point<f64, Logical>);
+
+/// Keeps every touch point on the surface it went down on until it is lifted,
+/// so dragging across windows does not hand the touch point to another window
+pub struct TouchDragGrab<BackendData: Backend + 'static> {
+    start_data: GrabStartData<WallyState<BackendData>>,
+    slots: HashMap<TouchSlot, Option<TouchFocus>>,
+}
+
+impl<BackendData: Backend + 'static> TouchGrab<WallyState<BackendData>>
+    for TouchDragGrab<BackendData>
+{
+    fn down(
+        &mut self,
+        data: &mut WallyState<BackendData>,
+        handle: &mut TouchInnerHandle<'_, WallyState<BackendData>>,
+        focus: Option<(
+            <WallyState<BackendData> as SeatHandler>::TouchFocus,
+            Point<f64, Logical>,
+        )>,
+        event: &DownEvent,
+        seq: Serial,
+    ) {
+        self.slots.insert(event.slot, focus.clone());
+        handle.down(data, focus, event, seq);
+    }
+
+    fn up(
+        &mut self,
+        data: &mut WallyState<BackendData>,
+        handle: &mut TouchInnerHandle<'_, WallyState<BackendData>>,
+        event: &UpEvent,
+        seq: Serial,
+    ) {
+        handle.up(data, event, seq);
+        self.slots.remove(&event.slot);
+
+        if self.slots.is_empty() {
+            handle.unset_grab(self, data);
+        }
+    }
+
+    fn motion(
+        &mut self,
+        data: &mut WallyState<BackendData>,
+        handle: &mut TouchInnerHandle<'_, WallyState<BackendData>>,
+        _focus: Option<(
+            <WallyState<BackendData> as SeatHandler>::TouchFocus,
+            Point<f64, Logical>,
+        )>,
+        event: &MotionEvent,
+        seq: Serial,
+    ) {
+        let focus = self
+            .slots
+            .get(&event.slot)
+            .cloned()
+            .flatten()
+            .filter(|(surface, _)| surface.alive());
+        handle.motion(data, focus, event, seq);
+    }
+
+    fn frame(
+        &mut self,
+        data: &mut WallyState<BackendData>,
+        handle: &mut TouchInnerHandle<'_, WallyState<BackendData>>,
+        seq: Serial,
+    ) {
+        handle.frame(data, seq);
+    }
+
+    fn cancel(
+        &mut self,
+        data: &mut WallyState<BackendData>,
+        handle: &mut TouchInnerHandle<'_, WallyState<BackendData>>,
+        seq: Serial,
+    ) {
+        handle.cancel(data, seq);
+        handle.unset_grab(self, data);
+    }
+
+    fn shape(
+        &mut self,
+        data: &mut WallyState<BackendData>,
+        handle: &mut TouchInnerHandle<'_, WallyState<BackendData>>,
+        event: &ShapeEvent,
+        seq: Serial,
+    ) {
+        handle.shape(data, event, seq);
+    }
+
+    fn orientation(
+        &mut self,
+        data: &mut WallyState<BackendData>,
+        handle: &mut TouchInnerHandle<'_, WallyState<BackendData>>,
+        event: &OrientationEvent,
+        seq: Serial,
+    ) {
+        handle.orientation(data, event, seq);
+    }
+
+    fn start_data(&self) -> &GrabStartData<WallyState<BackendData>> {
+        &self.start_data
+    }
+
+    fn unset(&mut self, _data: &mut WallyState<BackendData>) {}
+}
+
+impl<BackendData: Backend> WallyState<BackendData> {
+    fn touch_location<I: InputBackend>(
+        &self,
+        event: &impl AbsolutePositionEvent<I>,
+    ) -> Option<Point<f64, Logical>> {
+        self.mapped_position(event, &CONFIG.touch_outputs)
+    }
+
+    fn touch_focus(&self, location: Point<f64, Logical>) -> Option<TouchFocus> {
+        self.surface_under(location)
+            .map(|(target, location)| (target.into(), location))
+    }
+
+    pub fn on_touch_down<I: InputBackend>(&mut self, event: I::TouchDownEvent) {
+        let Some(location) = self.touch_location(&event) else {
+            return;
+        };
+
+        let serial = SERIAL_COUNTER.next_serial();
+        let touch = self.seat.get_touch().unwrap();
+        let focus = self.touch_focus(location);
+
+        // touching a window focuses and raises it, just like clicking it
+        self.focus_window_under(location, serial);
+
+        if !touch.is_grabbed() {
+            let start_data = GrabStartData {
+                focus: focus.clone(),
+                slot: event.slot(),
+                location,
+            };
+            touch.set_grab(
+                self,
+                TouchDragGrab {
+                    start_data,
+                    slots: HashMap::new(),
+                },
+                serial,
+            );
+        }
+
+        touch.down(
+            self,
+            focus,
+            &DownEvent {
+                slot: event.slot(),
+                location,
+                serial,
+                time: event.time_msec(),
+            },
+        );
+    }
+
+    pub fn on_touch_motion<I: InputBackend>(&mut self, event: I::TouchMotionEvent) {
+        let Some(location) = self.touch_location(&event) else {
+            return;
+        };
+
+        let touch = self.seat.get_touch().unwrap();
+        let focus = self.touch_focus(location);
+
+        touch.motion(
+            self,
+            focus,
+            &MotionEvent {
+                slot: event.slot(),
+                location,
+                time: event.time_msec(),
+            },
+        );
+    }
+
+    pub fn on_touch_up<I: InputBackend>(&mut self, event: I::TouchUpEvent) {
+        let touch = self.seat.get_touch().unwrap();
+
+        touch.up(
+            self,
+            &UpEvent {
+                slot: event.slot(),
+                serial: SERIAL_COUNTER.next_serial(),
+                time: event.time_msec(),
+            },
+        );
+    }
+
+    pub fn on_touch_frame(&mut self) {
+        let touch = self.seat.get_touch().unwrap();
+        touch.frame(self);
+    }
+
+    pub fn on_touch_cancel<I: InputBackend>(&mut self, _event: I::TouchCancelEvent) {
+        let touch = self.seat.get_touch().unwrap();
+        touch.cancel(self);
+    }
+}
@@ -0,0 +1,28 @@
+use super::keybind::Action;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SwipeDirection {
+    Horizontal,
+    Vertical,
+}
+
+/// A touchpad swipe handled by the compositor instead of being forwarded to clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SwipeGesture {
+    pub fingers: u32,
+    pub direction: SwipeDirection,
+}
+
+impl SwipeGesture {
+    pub fn new(fingers: u32, direction: SwipeDirection) -> Self {
+        Self { fingers, direction }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum GestureAction {
+    /// Move between workspaces, following the fingers
+    SwitchWorkspace,
+    /// Perform an action once the swipe ends far enough from where it started
+    Action(Action),
+}
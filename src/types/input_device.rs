@@ -0,0 +1,64 @@
+use smithay::reexports::input::{self, AccelProfile, DeviceCapability, ScrollMethod};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputDeviceKind {
+    Keyboard,
+    /// Any pointer that is not a touchpad, like mice and trackballs
+    Pointer,
+    Touchpad,
+}
+
+impl InputDeviceKind {
+    pub fn of(device: &input::Device) -> Option<Self> {
+        if device.has_capability(DeviceCapability::Pointer) {
+            // libinput only offers tapping on touchpads
+            if device.config_tap_finger_count() > 0 {
+                Some(Self::Touchpad)
+            } else {
+                Some(Self::Pointer)
+            }
+        } else if device.has_capability(DeviceCapability::Keyboard) {
+            Some(Self::Keyboard)
+        } else {
+            None
+        }
+    }
+}
+
+/// libinput settings for the devices matching by name and/or kind,
+/// unset options keep the libinput defaults
+#[derive(Debug, Clone, Default)]
+pub struct InputDeviceConfig {
+    pub name: Option<String>,
+    pub kind: Option<InputDeviceKind>,
+
+    pub tap: Option<bool>,
+    pub natural_scroll: Option<bool>,
+    pub accel_profile: Option<AccelProfile>,
+    /// From -1.0 to 1.0
+    pub accel_speed: Option<f64>,
+    /// Disable the touchpad while typing
+    pub dwt: Option<bool>,
+    pub scroll_method: Option<ScrollMethod>,
+    pub left_handed: Option<bool>,
+    /// Press left and right at the same time for a middle click
+    pub middle_emulation: Option<bool>,
+}
+
+impl InputDeviceConfig {
+    pub fn kind(kind: InputDeviceKind) -> Self {
+        Self {
+            kind: Some(kind),
+            ..Default::default()
+        }
+    }
+
+    /// A section matches when it has at least one criteria and all of its criteria match
+    pub fn matches(&self, device: &input::Device) -> bool {
+        (self.name.is_some() || self.kind.is_some())
+            && self.name.as_ref().is_none_or(|name| name == device.name())
+            && self
+                .kind
+                .is_none_or(|kind| InputDeviceKind::of(device) == Some(kind))
+    }
+}
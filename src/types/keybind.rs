@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use bitflags::bitflags;
 use smithay::input::keyboard::{Keysym, ModifiersState};
 
@@ -54,12 +56,82 @@ impl Keybind {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MouseTrigger {
+    /// Linux button code, see `input-event-codes.h`
+    Button(u32),
+    ScrollUp,
+    ScrollDown,
+    ScrollLeft,
+    ScrollRight,
+}
+
+impl MouseTrigger {
+    pub const LEFT: Self = Self::Button(0x110);
+    pub const RIGHT: Self = Self::Button(0x111);
+    pub const MIDDLE: Self = Self::Button(0x112);
+}
+
+/// A mouse button or scroll direction held together with modifiers,
+/// bound buttons and scrolling are not forwarded to clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MouseBind {
+    pub modifiers: KeyModifiers,
+    pub trigger: MouseTrigger,
+}
+
+impl MouseBind {
+    pub fn new(modifiers: impl Into<KeyModifiers>, trigger: MouseTrigger) -> Self {
+        Self {
+            modifiers: modifiers.into(),
+            trigger,
+        }
+    }
+}
+
+/// What a keybind does, and when
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub action: Action,
+    /// Trigger once the key is released, only if no other key was pressed in between.
+    /// The key itself is still forwarded to clients, e.g. to tap Super to open a launcher
+    pub on_release: bool,
+    /// Keep triggering at the configured repeat rate while the key is held
+    pub repeat: bool,
+    /// Still trigger while the session is locked
+    pub allow_when_locked: bool,
+    /// Still trigger while a client inhibits keyboard shortcuts
+    pub allow_when_inhibited: bool,
+}
+
+impl From<Action> for Binding {
+    fn from(action: Action) -> Self {
+        Self {
+            action,
+            on_release: false,
+            repeat: false,
+            allow_when_locked: false,
+            allow_when_inhibited: false,
+        }
+    }
+}
+
+/// A named set of keybinds replacing the regular ones while it is active, like i3's modes.
+/// Escape returns to the regular keybinds unless the mode binds it
+#[derive(Debug, Clone, Default)]
+pub struct BindingMode {
+    pub keybinds: HashMap<Keybind, Binding>,
+    /// Forward unbound keys to clients instead of swallowing them
+    pub passthrough: bool,
+}
+
 #[derive(Debug, Clone)]
 pub enum Action {
     Quit,
     NextWorkspace,
     PrevWorkspace,
     Spawn(String),
+    ToggleScratchpad(String),
     MoveWindowToPrevWorkspace, // TODO
     MoveWindowToNextWorkspace, // TODO
     MoveWindowFloating,        // TODO
@@ -67,4 +139,10 @@ pub enum Action {
     MoveWindowBack,            // TODO
     MoveWindowNext,            // TODO
     RemoveWindow,
+    /// Cycle to the next layout of the keymap
+    SwitchLayout,
+    /// Switch to the keybinds of a binding mode
+    EnterMode(String),
+    /// Return to the regular keybinds
+    ExitMode,
 }
@@ -1 +1,6 @@
+pub mod gesture;
+pub mod input_device;
 pub mod keybind;
+pub mod output_config;
+pub mod window_rule;
+pub mod xkb;
@@ -0,0 +1,168 @@
+use smithay::{
+    output::Output,
+    utils::{Logical, Physical, Point, Size, Transform},
+};
+
+/// Serial number of an output, read from its EDID by backends driving real outputs
+#[derive(Debug, Clone)]
+pub struct OutputSerial(pub String);
+
+/// Settings for the outputs matching by connector name and/or make, model and serial
+#[derive(Debug, Clone, Default)]
+pub struct OutputConfig {
+    /// Connector name, e.g. `DP-1`
+    pub name: Option<String>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+
+    /// Size of the mode in pixels
+    pub mode: Option<Size<i32, Physical>>,
+    /// Refresh rate in mHz, the highest available one is used if unset
+    pub refresh: Option<i32>,
+    pub position: Option<Point<i32, Logical>>,
+    pub scale: Option<f64>,
+    pub transform: Option<Transform>,
+    /// Outputs are enabled unless configured otherwise
+    pub enabled: Option<bool>,
+}
+
+impl OutputConfig {
+    pub fn name(name: &str) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..Default::default()
+        }
+    }
+
+    pub fn make_model(make: &str, model: &str) -> Self {
+        Self {
+            make: Some(make.into()),
+            model: Some(model.into()),
+            ..Default::default()
+        }
+    }
+
+    /// A section matches when it has at least one criteria and all of its criteria match
+    pub fn matches(&self, output: &Output) -> bool {
+        let properties = output.physical_properties();
+        let serial = output
+            .user_data()
+            .get::<OutputSerial>()
+            .map(|serial| serial.0.as_str());
+
+        fn is_match(criteria: &Option<String>, value: Option<&str>) -> bool {
+            criteria
+                .as_ref()
+                .is_none_or(|criteria| Some(criteria.as_str()) == value)
+        }
+
+        (self.name.is_some()
+            || self.make.is_some()
+            || self.model.is_some()
+            || self.serial.is_some())
+            && is_match(&self.name, Some(&output.name()))
+            && is_match(&self.make, Some(&properties.make))
+            && is_match(&self.model, Some(&properties.model))
+            && is_match(&self.serial, serial)
+    }
+
+    /// Merge all sections matching an output into a single one,
+    /// where later sections take precedence over earlier ones
+    pub fn resolve<'a>(
+        configs: impl IntoIterator<Item = &'a OutputConfig>,
+        output: &Output,
+    ) -> OutputConfig {
+        configs
+            .into_iter()
+            .filter(|config| config.matches(output))
+            .fold(OutputConfig::default(), |mut resolved, config| {
+                resolved.mode = config.mode.or(resolved.mode);
+                resolved.refresh = config.refresh.or(resolved.refresh);
+                resolved.position = config.position.or(resolved.position);
+                resolved.scale = config.scale.or(resolved.scale);
+                resolved.transform = config.transform.or(resolved.transform);
+                resolved.enabled = config.enabled.or(resolved.enabled);
+                resolved
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use smithay::output::{PhysicalProperties, Subpixel};
+
+    use super::*;
+
+    fn output(name: &str, serial: Option<&str>) -> Output {
+        let output = Output::new(
+            name.into(),
+            PhysicalProperties {
+                size: (0, 0).into(),
+                subpixel: Subpixel::Unknown,
+                make: "Dell".into(),
+                model: "U2720Q".into(),
+            },
+        );
+        if let Some(serial) = serial {
+            output
+                .user_data()
+                .insert_if_missing(|| OutputSerial(serial.into()));
+        }
+        output
+    }
+
+    #[test]
+    fn section_without_criteria_matches_nothing() {
+        let config = OutputConfig {
+            scale: Some(2.0),
+            ..Default::default()
+        };
+
+        assert!(!config.matches(&output("DP-1", None)));
+    }
+
+    #[test]
+    fn every_criteria_has_to_match() {
+        let config = OutputConfig {
+            serial: Some("ABC123".into()),
+            ..OutputConfig::make_model("Dell", "U2720Q")
+        };
+
+        assert!(config.matches(&output("DP-1", Some("ABC123"))));
+        assert!(!config.matches(&output("DP-1", Some("XYZ789"))));
+        // outputs without a known serial can't match a serial
+        assert!(!config.matches(&output("DP-1", None)));
+        assert!(!OutputConfig::make_model("Dell", "P2419H").matches(&output("DP-1", None)));
+        assert!(OutputConfig::name("DP-1").matches(&output("DP-1", None)));
+        assert!(!OutputConfig::name("DP-1").matches(&output("HDMI-A-1", None)));
+    }
+
+    #[test]
+    fn later_sections_take_precedence() {
+        let configs = [
+            OutputConfig {
+                scale: Some(2.0),
+                mode: Some((3840, 2160).into()),
+                ..OutputConfig::make_model("Dell", "U2720Q")
+            },
+            OutputConfig {
+                scale: Some(1.5),
+                position: Some((1920, 0).into()),
+                ..OutputConfig::name("DP-1")
+            },
+            OutputConfig {
+                enabled: Some(false),
+                ..OutputConfig::name("HDMI-A-1")
+            },
+        ];
+
+        let resolved = OutputConfig::resolve(&configs, &output("DP-1", None));
+
+        assert_eq!(resolved.scale, Some(1.5));
+        assert_eq!(resolved.mode, Some((3840, 2160).into()));
+        assert_eq!(resolved.position, Some((1920, 0).into()));
+        assert_eq!(resolved.enabled, None);
+        assert_eq!(resolved.name, None);
+    }
+}
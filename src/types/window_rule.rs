@@ -0,0 +1,260 @@
+use regex::Regex;
+use smithay::utils::{Logical, Point, Rectangle, Size};
+
+/// A rule applied to a window when it is first committed, matched by regex on
+/// its app_id and/or title. Only `opacity` and `no_border` are applied again when
+/// the app_id or title changes later on
+#[derive(Debug, Clone, Default)]
+pub struct WindowRule {
+    pub app_id: Option<Regex>,
+    pub title: Option<Regex>,
+
+    /// 1-based index of the workspace the window should be placed on
+    pub workspace: Option<usize>,
+    /// Name of the output the window should be placed on
+    pub monitor: Option<String>,
+    pub floating: bool,
+    pub size: Option<Size<i32, Logical>>,
+    /// Position relative to the monitor the window is placed on
+    pub position: Option<Point<i32, Logical>>,
+    pub fullscreen: bool,
+    pub opacity: Option<f32>,
+    pub no_border: bool,
+    /// Prevent the window from taking focus when it is mapped
+    pub no_focus: bool,
+    /// Name of the scratchpad the window should be used as
+    pub scratchpad: Option<String>,
+}
+
+impl WindowRule {
+    pub fn app_id(pattern: &str) -> Self {
+        Self {
+            app_id: Some(Regex::new(pattern).expect("invalid app_id pattern")),
+            ..Default::default()
+        }
+    }
+
+    pub fn title(pattern: &str) -> Self {
+        Self {
+            title: Some(Regex::new(pattern).expect("invalid title pattern")),
+            ..Default::default()
+        }
+    }
+
+    /// A rule matches when it has at least one pattern and all of its patterns match
+    pub fn matches(&self, app_id: Option<&str>, title: Option<&str>) -> bool {
+        fn is_match(regex: &Option<Regex>, value: Option<&str>) -> bool {
+            match regex {
+                Some(regex) => value.is_some_and(|value| regex.is_match(value)),
+                None => true,
+            }
+        }
+
+        (self.app_id.is_some() || self.title.is_some())
+            && is_match(&self.app_id, app_id)
+            && is_match(&self.title, title)
+    }
+
+    /// Merge all rules matching the given app_id and title into a single rule,
+    /// where later rules take precedence over earlier ones
+    pub fn resolve<'a>(
+        rules: impl IntoIterator<Item = &'a WindowRule>,
+        app_id: Option<&str>,
+        title: Option<&str>,
+    ) -> WindowRule {
+        rules
+            .into_iter()
+            .filter(|rule| rule.matches(app_id, title))
+            .fold(WindowRule::default(), |mut resolved, rule| {
+                resolved.workspace = rule.workspace.or(resolved.workspace);
+                resolved.monitor = rule.monitor.clone().or(resolved.monitor);
+                resolved.floating |= rule.floating;
+                resolved.size = rule.size.or(resolved.size);
+                resolved.position = rule.position.or(resolved.position);
+                resolved.fullscreen |= rule.fullscreen;
+                resolved.opacity = rule.opacity.or(resolved.opacity);
+                resolved.no_border |= rule.no_border;
+                resolved.no_focus |= rule.no_focus;
+                resolved.scratchpad = rule.scratchpad.clone().or(resolved.scratchpad);
+                resolved
+            })
+    }
+
+    /// Index of the workspace a window is placed on out of `workspace_count`,
+    /// `fallback` if the rule names none or one that doesn't exist
+    pub fn workspace_index(&self, workspace_count: usize, fallback: usize) -> usize {
+        self.workspace
+            .map(|workspace| workspace.saturating_sub(1))
+            .filter(|&workspace| workspace < workspace_count)
+            .unwrap_or(fallback)
+    }
+
+    /// Location of a window of `size` at `location` on the output at `current_output`,
+    /// once it is placed on the output at `output`
+    pub fn placement(
+        &self,
+        location: Point<i32, Logical>,
+        size: Size<i32, Logical>,
+        current_output: Rectangle<i32, Logical>,
+        output: Rectangle<i32, Logical>,
+    ) -> Point<i32, Logical> {
+        if self.fullscreen {
+            output.loc
+        } else if let Some(position) = self.position {
+            output.loc + position
+        } else if self.floating {
+            // floating windows are centered on their output
+            let size = self.size.unwrap_or(size);
+            output.loc
+                + Point::from((
+                    (output.size.w - size.w).max(0) / 2,
+                    (output.size.h - size.h).max(0) / 2,
+                ))
+        } else {
+            location - current_output.loc + output.loc
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rule_without_patterns_matches_nothing() {
+        let rule = WindowRule {
+            floating: true,
+            ..Default::default()
+        };
+
+        assert!(!rule.matches(Some("foot"), Some("foot")));
+        assert!(!rule.matches(None, None));
+    }
+
+    #[test]
+    fn every_pattern_has_to_match() {
+        let rule = WindowRule {
+            title: Some(Regex::new("^vim").unwrap()),
+            ..WindowRule::app_id("^foot$")
+        };
+
+        assert!(rule.matches(Some("foot"), Some("vim notes.txt")));
+        assert!(!rule.matches(Some("foot"), Some("htop")));
+        assert!(!rule.matches(Some("footclient"), Some("vim notes.txt")));
+        // a missing title can't match a title pattern
+        assert!(!rule.matches(Some("foot"), None));
+    }
+
+    #[test]
+    fn later_rules_take_precedence() {
+        let rules = [
+            WindowRule {
+                workspace: Some(2),
+                opacity: Some(0.5),
+                floating: true,
+                ..WindowRule::app_id("^foot$")
+            },
+            WindowRule {
+                workspace: Some(3),
+                no_border: true,
+                ..WindowRule::title("vim")
+            },
+            WindowRule {
+                workspace: Some(4),
+                ..WindowRule::app_id("^firefox$")
+            },
+        ];
+
+        let resolved = WindowRule::resolve(&rules, Some("foot"), Some("vim"));
+
+        assert_eq!(resolved.workspace, Some(3));
+        assert_eq!(resolved.opacity, Some(0.5));
+        // flags set by any matching rule stay set
+        assert!(resolved.floating);
+        assert!(resolved.no_border);
+        assert!(resolved.app_id.is_none());
+    }
+
+    #[test]
+    fn resolving_without_matches_is_the_default() {
+        let rules = [WindowRule {
+            floating: true,
+            ..WindowRule::app_id("^foot$")
+        }];
+
+        let resolved = WindowRule::resolve(&rules, Some("firefox"), None);
+
+        assert!(!resolved.floating);
+        assert_eq!(resolved.workspace, None);
+    }
+
+    fn rect(x: i32, y: i32, w: i32, h: i32) -> Rectangle<i32, Logical> {
+        Rectangle::new((x, y).into(), (w, h).into())
+    }
+
+    #[test]
+    fn workspace_falls_back_when_missing() {
+        let rule = WindowRule {
+            workspace: Some(3),
+            ..WindowRule::app_id("^foot$")
+        };
+
+        assert_eq!(rule.workspace_index(5, 0), 2);
+        // there is no third workspace to go to
+        assert_eq!(rule.workspace_index(2, 1), 1);
+        assert_eq!(WindowRule::app_id("^foot$").workspace_index(5, 4), 4);
+    }
+
+    #[test]
+    fn tiled_windows_keep_their_place_on_the_new_output() {
+        let rule = WindowRule::app_id("^foot$");
+        let location = rule.placement(
+            (100, 50).into(),
+            (640, 480).into(),
+            rect(0, 0, 1920, 1080),
+            rect(1920, 0, 2560, 1440),
+        );
+
+        assert_eq!(location, (2020, 50).into());
+    }
+
+    #[test]
+    fn floating_windows_are_centered() {
+        let rule = WindowRule {
+            floating: true,
+            ..WindowRule::app_id("^foot$")
+        };
+        let output = rect(1920, 0, 1920, 1080);
+
+        let location = rule.placement((0, 0).into(), (640, 480).into(), output, output);
+        assert_eq!(location, (1920 + 640, 300).into());
+
+        // a configured size is what gets centered, oversized windows stick to the corner
+        let rule = WindowRule {
+            size: Some((2000, 2000).into()),
+            ..rule
+        };
+        let location = rule.placement((0, 0).into(), (640, 480).into(), output, output);
+        assert_eq!(location, (1920, 0).into());
+    }
+
+    #[test]
+    fn position_and_fullscreen_take_precedence() {
+        let output = rect(1920, 0, 1920, 1080);
+        let rule = WindowRule {
+            floating: true,
+            position: Some((10, 20).into()),
+            ..WindowRule::app_id("^foot$")
+        };
+
+        let location = rule.placement((0, 0).into(), (640, 480).into(), output, output);
+        assert_eq!(location, (1930, 20).into());
+
+        let rule = WindowRule {
+            fullscreen: true,
+            ..rule
+        };
+        let location = rule.placement((0, 0).into(), (640, 480).into(), output, output);
+        assert_eq!(location, (1920, 0).into());
+    }
+}
@@ -0,0 +1,27 @@
+use smithay::input::keyboard::XkbConfig;
+
+/// Keymap settings handed to xkbcommon, empty strings use the xkb defaults.
+/// Multiple layouts are separated by commas and cycled with `Action::SwitchLayout`
+#[derive(Debug, Clone, Default)]
+pub struct XkbSettings {
+    pub rules: String,
+    pub model: String,
+    /// e.g. `us,se`
+    pub layout: String,
+    /// Variants for each layout, e.g. `dvorak,`
+    pub variant: String,
+    /// e.g. `caps:escape,compose:ralt`
+    pub options: Option<String>,
+}
+
+impl XkbSettings {
+    pub fn xkb_config(&self) -> XkbConfig<'_> {
+        XkbConfig {
+            rules: &self.rules,
+            model: &self.model,
+            layout: &self.layout,
+            variant: &self.variant,
+            options: self.options.clone(),
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use std::path::PathBuf;
+
+use smithay::{
+    backend::input::{
+        Axis, AxisRelativeDirection, AxisSource, ButtonState, Device, DeviceCapability, Event,
+        InputBackend, InputEvent, KeyState, KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent,
+        PointerMotionEvent, UnusedEvent,
+    },
+    input::keyboard::{Keycode, Layout, ModifiersState, SerializedMods},
+    reexports::wayland_server::backend::ObjectId,
+    utils::{Logical, Point},
+};
+
+use crate::{backend::Backend, config::CONFIG, state::WallyState};
+
+/// Input backend for the events of virtual keyboards and pointers created by clients,
+/// so they are handled by `process_input_event` like the ones of physical devices
+#[derive(Debug)]
+pub struct VirtualInput;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VirtualDevice {
+    /// Id of the protocol object the device belongs to
+    pub id: ObjectId,
+    pub capability: DeviceCapability,
+}
+
+impl Device for VirtualDevice {
+    fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn name(&self) -> String {
+        match self.capability {
+            DeviceCapability::Keyboard => "virtual-keyboard".into(),
+            _ => "virtual-pointer".into(),
+        }
+    }
+
+    fn has_capability(&self, capability: DeviceCapability) -> bool {
+        self.capability == capability
+    }
+
+    fn usb_id(&self) -> Option<(u32, u32)> {
+        None
+    }
+
+    fn syspath(&self) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// Common part of every virtual event, protocol timestamps are in milliseconds
+#[derive(Debug)]
+pub struct VirtualEvent<T> {
+    pub device: VirtualDevice,
+    pub time: u32,
+    pub inner: T,
+}
+
+impl<T> Event<VirtualInput> for VirtualEvent<T> {
+    fn time(&self) -> u64 {
+        self.time as u64 * 1000
+    }
+
+    fn device(&self) -> VirtualDevice {
+        self.device.clone()
+    }
+}
+
+#[derive(Debug)]
+pub struct VirtualKey {
+    /// evdev key code, as sent by the client
+    pub key: u32,
+    pub state: KeyState,
+}
+
+impl KeyboardKeyEvent<VirtualInput> for VirtualEvent<VirtualKey> {
+    fn key_code(&self) -> Keycode {
+        // xkb key codes are offset by 8 from evdev ones
+        (self.inner.key + 8).into()
+    }
+
+    fn state(&self) -> KeyState {
+        self.inner.state
+    }
+
+    fn count(&self) -> u32 {
+        match self.inner.state {
+            KeyState::Pressed => 1,
+            KeyState::Released => 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct VirtualMotion {
+    pub delta: Point<f64, Logical>,
+}
+
+impl PointerMotionEvent<VirtualInput> for VirtualEvent<VirtualMotion> {
+    fn delta_x(&self) -> f64 {
+        self.inner.delta.x
+    }
+
+    fn delta_y(&self) -> f64 {
+        self.inner.delta.y
+    }
+
+    fn delta_x_unaccel(&self) -> f64 {
+        self.inner.delta.x
+    }
+
+    fn delta_y_unaccel(&self) -> f64 {
+        self.inner.delta.y
+    }
+}
+
+#[derive(Debug)]
+pub struct VirtualButton {
+    pub button: u32,
+    pub state: ButtonState,
+}
+
+impl PointerButtonEvent<VirtualInput> for VirtualEvent<VirtualButton> {
+    fn button_code(&self) -> u32 {
+        self.inner.button
+    }
+
+    fn state(&self) -> ButtonState {
+        self.inner.state
+    }
+}
+
+/// Scrolling collected until the client sends a frame
+#[derive(Debug, Default, Clone, Copy)]
+pub struct VirtualAxis {
+    pub source: Option<AxisSource>,
+    pub horizontal: Option<f64>,
+    pub vertical: Option<f64>,
+    pub horizontal_v120: Option<f64>,
+    pub vertical_v120: Option<f64>,
+}
+
+impl VirtualAxis {
+    pub fn is_empty(&self) -> bool {
+        self.horizontal.is_none() && self.vertical.is_none()
+    }
+}
+
+impl PointerAxisEvent<VirtualInput> for VirtualEvent<VirtualAxis> {
+    fn amount(&self, axis: Axis) -> Option<f64> {
+        match axis {
+            Axis::Horizontal => self.inner.horizontal,
+            Axis::Vertical => self.inner.vertical,
+        }
+    }
+
+    fn amount_v120(&self, axis: Axis) -> Option<f64> {
+        match axis {
+            Axis::Horizontal => self.inner.horizontal_v120,
+            Axis::Vertical => self.inner.vertical_v120,
+        }
+    }
+
+    fn source(&self) -> AxisSource {
+        self.inner.source.unwrap_or(AxisSource::Wheel)
+    }
+
+    fn relative_direction(&self, _axis: Axis) -> AxisRelativeDirection {
+        AxisRelativeDirection::Identical
+    }
+}
+
+impl InputBackend for VirtualInput {
+    type Device = VirtualDevice;
+    type KeyboardKeyEvent = VirtualEvent<VirtualKey>;
+    type PointerAxisEvent = VirtualEvent<VirtualAxis>;
+    type PointerButtonEvent = VirtualEvent<VirtualButton>;
+    type PointerMotionEvent = VirtualEvent<VirtualMotion>;
+    // absolute motion is mapped to the outputs by the protocol handler
+    type PointerMotionAbsoluteEvent = UnusedEvent;
+    type GestureSwipeBeginEvent = UnusedEvent;
+    type GestureSwipeUpdateEvent = UnusedEvent;
+    type GestureSwipeEndEvent = UnusedEvent;
+    type GesturePinchBeginEvent = UnusedEvent;
+    type GesturePinchUpdateEvent = UnusedEvent;
+    type GesturePinchEndEvent = UnusedEvent;
+    type GestureHoldBeginEvent = UnusedEvent;
+    type GestureHoldEndEvent = UnusedEvent;
+    type TouchDownEvent = UnusedEvent;
+    type TouchUpEvent = UnusedEvent;
+    type TouchMotionEvent = UnusedEvent;
+    type TouchCancelEvent = UnusedEvent;
+    type TouchFrameEvent = UnusedEvent;
+    type TabletToolAxisEvent = UnusedEvent;
+    type TabletToolProximityEvent = UnusedEvent;
+    type TabletToolTipEvent = UnusedEvent;
+    type TabletToolButtonEvent = UnusedEvent;
+    type SwitchToggleEvent = UnusedEvent;
+    type SpecialEvent = UnusedEvent;
+}
+
+/// Virtual keyboards bring their own keymap, which replaces the configured one on the
+/// seat while they type, until a physical keyboard is used again
+#[derive(Debug, Default)]
+pub struct VirtualKeymapState {
+    /// Virtual keyboard whose keymap is currently set on the seat
+    owner: Option<ObjectId>,
+    /// Layout active before the configured keymap was replaced
+    layout: Option<Layout>,
+    /// Set while a virtual keyboard's event is processed
+    injecting: bool,
+}
+
+impl<BackendData: Backend> WallyState<BackendData> {
+    /// Set the keymap of a virtual keyboard on the seat, unless it already is
+    fn use_virtual_keymap(&mut self, keyboard: &ObjectId, keymap: &str) {
+        if self.virtual_keymap_state.owner.as_ref() == Some(keyboard) {
+            return;
+        }
+
+        let seat_keyboard = self.seat.get_keyboard().unwrap();
+
+        if self.virtual_keymap_state.owner.is_none() {
+            let layout = seat_keyboard.with_xkb_state(self, |context| {
+                context.xkb().lock().unwrap().active_layout()
+            });
+            self.virtual_keymap_state.layout = Some(layout);
+        }
+
+        if let Err(err) = seat_keyboard.set_keymap_from_string(self, keymap.to_owned()) {
+            tracing::warn!("Failed to set the keymap of a virtual keyboard: {err}");
+            return;
+        }
+
+        self.virtual_keymap_state.owner = Some(keyboard.clone());
+    }
+
+    /// Put the configured keymap back once a physical keyboard is used
+    pub fn restore_keymap(&mut self) {
+        if self.virtual_keymap_state.injecting || self.virtual_keymap_state.owner.is_none() {
+            return;
+        }
+
+        let keyboard = self.seat.get_keyboard().unwrap();
+        if let Err(err) = keyboard.set_xkb_config(self, CONFIG.xkb.xkb_config()) {
+            tracing::warn!("Failed to restore the keymap: {err}");
+        }
+
+        if let Some(layout) = self.virtual_keymap_state.layout.take() {
+            keyboard.with_xkb_state(self, |mut context| context.set_layout(layout));
+        }
+
+        self.virtual_keymap_state.owner = None;
+    }
+
+    /// A virtual keyboard is gone, its keymap can't stay on the seat
+    pub fn virtual_keyboard_destroyed(&mut self, keyboard: &ObjectId) {
+        if self.virtual_keymap_state.owner.as_ref() == Some(keyboard) {
+            self.restore_keymap();
+        }
+    }
+
+    pub fn virtual_key(&mut self, keymap: &str, event: VirtualEvent<VirtualKey>) {
+        self.use_virtual_keymap(&event.device.id, keymap);
+
+        self.virtual_keymap_state.injecting = true;
+        self.process_input_event(InputEvent::<VirtualInput>::Keyboard { event });
+        self.virtual_keymap_state.injecting = false;
+    }
+
+    /// Modifier masks of a virtual keyboard, interpreted with its keymap.
+    /// Modifiers are mapped by their core X11 bits, which every xkb keymap uses
+    pub fn virtual_modifiers(
+        &mut self,
+        keyboard: &ObjectId,
+        keymap: &str,
+        serialized: SerializedMods,
+    ) {
+        self.use_virtual_keymap(keyboard, keymap);
+
+        let mods = serialized.depressed | serialized.latched | serialized.locked;
+        let seat_keyboard = self.seat.get_keyboard().unwrap();
+        seat_keyboard.set_modifier_state(ModifiersState {
+            shift: mods & (1 << 0) != 0,
+            caps_lock: serialized.locked & (1 << 1) != 0,
+            ctrl: mods & (1 << 2) != 0,
+            alt: mods & (1 << 3) != 0,
+            num_lock: serialized.locked & (1 << 4) != 0,
+            logo: mods & (1 << 6) != 0,
+            serialized,
+            ..Default::default()
+        });
+    }
+}
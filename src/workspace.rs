@@ -1,16 +1,94 @@
-use std::collections::VecDeque;
+use smithay::{
+    desktop::Space,
+    utils::{Logical, Point},
+};
 
-use crate::state::ClientState;
+use crate::elements::window::WindowElement;
 
 #[derive(Debug)]
 pub struct Workspace {
-    clients: VecDeque<ClientState>,
+    /// Windows of the workspace together with the location they
+    /// had in the space when the workspace was last visible
+    windows: Vec<(WindowElement, Point<i32, Logical>)>,
 }
 
 impl Default for Workspace {
     fn default() -> Self {
         Self {
-            clients: VecDeque::new(),
+            windows: Vec::new(),
+        }
+    }
+}
+
+impl Workspace {
+    pub fn add_window(&mut self, window: WindowElement, location: Point<i32, Logical>) {
+        self.windows.push((window, location));
+    }
+
+    /// Remove a window from the workspace, returning its last known location
+    pub fn remove_window(&mut self, window: &WindowElement) -> Option<Point<i32, Logical>> {
+        let index = self.windows.iter().position(|(w, _)| w == window)?;
+        Some(self.windows.remove(index).1)
+    }
+
+    pub fn contains(&self, window: &WindowElement) -> bool {
+        self.windows.iter().any(|(w, _)| w == window)
+    }
+
+    pub fn windows(&self) -> impl Iterator<Item = &WindowElement> {
+        self.windows.iter().map(|(window, _)| window)
+    }
+
+    /// Shift the last known location of all windows, e.g. when their output moved
+    pub fn translate(&mut self, offset: Point<i32, Logical>) {
+        for (_, location) in self.windows.iter_mut() {
+            *location += offset;
+        }
+    }
+
+    /// Take over the windows of another workspace
+    pub fn append(&mut self, other: Workspace) {
+        self.windows.extend(other.windows);
+    }
+
+    /// Windows in stacking order, floating windows stay above all others
+    fn stacked(&self) -> impl Iterator<Item = &(WindowElement, Point<i32, Logical>)> {
+        let (floating, tiled): (Vec<_>, Vec<_>) = self
+            .windows
+            .iter()
+            .partition(|(window, _)| window.is_floating());
+        tiled.into_iter().chain(floating)
+    }
+
+    /// Map all windows of the workspace onto the space at their last known location
+    pub fn map(&self, space: &mut Space<WindowElement>) {
+        for (window, location) in self.stacked() {
+            space.map_element(window.clone(), *location, false);
+        }
+    }
+
+    /// Map all windows shifted by `offset` from their last known location,
+    /// which stays untouched so the workspace can be mapped normally later
+    pub fn map_offset(&self, space: &mut Space<WindowElement>, offset: Point<i32, Logical>) {
+        for (window, location) in self.stacked() {
+            space.map_element(window.clone(), *location + offset, false);
+        }
+    }
+
+    /// Unmap all windows of the workspace without remembering their location
+    pub fn hide(&self, space: &mut Space<WindowElement>) {
+        for (window, _) in &self.windows {
+            space.unmap_elem(window);
+        }
+    }
+
+    /// Unmap all windows of the workspace from the space, remembering their location
+    pub fn unmap(&mut self, space: &mut Space<WindowElement>) {
+        for (window, location) in self.windows.iter_mut() {
+            if let Some(current) = space.element_location(window) {
+                *location = current;
+            }
+            space.unmap_elem(window);
         }
     }
 }